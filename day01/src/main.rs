@@ -1,4 +1,8 @@
-use utils::read_lines;
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+
+use utils::read_input;
 
 
 fn main() {
@@ -6,12 +10,130 @@ fn main() {
     part_2();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Instruction {
     Left(isize),
     Right(isize),
 }
 
+#[derive(Debug, PartialEq)]
+enum InstructionError {
+    UnknownDirection(char),
+    MissingAmount,
+    InvalidAmount(String),
+    Overflow(String),
+}
+
+#[derive(Debug)]
+struct ParseError {
+    line: usize,
+    kind: InstructionError,
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstructionError::UnknownDirection(c) => write!(f, "unknown direction {c:?}, expected 'L' or 'R'"),
+            InstructionError::MissingAmount => write!(f, "missing rotation amount"),
+            InstructionError::InvalidAmount(s) => write!(f, "invalid rotation amount {s:?}"),
+            InstructionError::Overflow(s) => write!(f, "rotation amount {s} is too large"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = InstructionError;
+
+    fn from_str(s: &str) -> Result<Instruction, InstructionError> {
+        let mut chars = s.chars();
+        let direction = chars.next().ok_or(InstructionError::MissingAmount)?;
+        let instruction = match direction {
+            'L' => Instruction::Left,
+            'R' => Instruction::Right,
+            c => return Err(InstructionError::UnknownDirection(c)),
+        };
+        let amount = chars.as_str();
+
+        if amount.is_empty() {
+            return Err(InstructionError::MissingAmount);
+        }
+        // The amount is a bare click count, `parse` alone would let `R+5` through
+        if !amount.chars().all(|c| c.is_ascii_digit()) {
+            return Err(InstructionError::InvalidAmount(amount.to_string()));
+        }
+        let n = amount.parse::<isize>()
+            .map_err(|e| match e.kind() {
+                IntErrorKind::PosOverflow => InstructionError::Overflow(amount.to_string()),
+                _ => InstructionError::InvalidAmount(amount.to_string()),
+            })?;
+        Ok(instruction(n))
+    }
+}
+
+#[derive(Debug)]
+struct Dial {
+    size: isize,
+    position: isize,
+}
+
+#[derive(Debug)]
+struct RotationReport {
+    end: isize,
+    zero_crossings: isize,
+}
+
+impl Dial {
+    fn new(size: isize, start: isize) -> Dial {
+        Dial { size, position: start.rem_euclid(size) }
+    }
+
+    fn rotate(&mut self, instruction: Instruction) -> RotationReport {
+        // Count how often the dial points at 0 during the rotation, including the end position.
+        // Every full turn passes 0 once, the rest is less than a turn so adding it can not overflow.
+        // Turning left is mirrored into turning right so both directions share the same formula
+        let (n, step) = match instruction {
+            Instruction::Left(n) => (n, -1),
+            Instruction::Right(n) => (n, 1),
+        };
+        let (full_turns, rest) = (n / self.size, n % self.size);
+        let from = if step < 0 {
+            (self.size - self.position) % self.size
+        }
+        else {
+            self.position
+        };
+        let zero_crossings = full_turns + (from + rest) / self.size;
+        self.position = (self.position + step * rest).rem_euclid(self.size);
+
+        RotationReport { end: self.position, zero_crossings }
+    }
+
+    // Reference implementation that moves one click at a time
+    #[cfg(test)]
+    fn rotate_slow(&mut self, instruction: Instruction) -> RotationReport {
+        let (step, n) = match instruction {
+            Instruction::Left(n) => (-1, n),
+            Instruction::Right(n) => (1, n),
+        };
+        let mut zero_crossings = 0;
+
+        for _ in 0..n {
+            self.position = (self.position + step).rem_euclid(self.size);
+            if self.position == 0 {
+                zero_crossings += 1;
+            }
+        }
+
+        RotationReport { end: self.position, zero_crossings }
+    }
+}
+
 fn part_1() {
     let input = parse("inputs/day01pt1.txt");
     let end_zeros = count_zero_endpoints_in_cycle(&input);
@@ -25,76 +147,101 @@ fn part_2() {
 }
 
 fn count_zero_endpoints_in_cycle(instructions: &[Instruction]) -> usize {
-    let mut current = 50;
-    let mut end_counts = 0;
+    let mut dial = Dial::new(100, 50);
 
     instructions.iter()
-        .for_each(|instruction| {
-            let old = current;
-            match instruction {
-                Instruction::Left(n) => {
-                    current -= n;
-                },
-                Instruction::Right(n) => {
-                    current += n;
-                },
+        .fold(0, |end_counts, &instruction| {
+            if dial.rotate(instruction).end == 0 {
+                end_counts + 1
             }
-            current = current.rem_euclid(100);
-            if current == 0 {
-                end_counts += 1;
+            else {
+                end_counts
             }
-        });
-
-    end_counts
+        })
 }
 
 fn solve_part_two(instructions: &[Instruction]) -> isize {
-    let mut hits = 0;
-    let mut current = 50;
+    let mut dial = Dial::new(100, 50);
 
     instructions.iter()
-        .for_each(|instruction| {
-            let dist : isize;
-
-            match instruction {
-                Instruction::Left(n) => {
-                    // Starting from 0 the distance is different since it will not cross 0 initially
-                    if current == 0 {
-                        dist = 100;
-                    }
-                    else {
-                        dist = current;
-                    }
-                    if *n >= dist {
-                        hits += 1 + (n - dist)/ 100;
-                    }
-                    current -= n;
-                },
-                Instruction::Right(n) => {
-                    dist = 100 - current;
-                    if *n >= dist {
-                        hits += 1 + (n - dist) / 100;
-                    }
-                    current += n;
-                }
-            }
-            current = current.rem_euclid(100);
-
-        });
-    hits
+        .fold(0, |hits, &instruction| hits + dial.rotate(instruction).zero_crossings)
 }
 
 fn parse(filename: &str) -> Vec<Instruction> {
-    read_lines(filename)
-        .into_iter()
-        .map(|line| {
-            let num = line[1..].parse::<isize>().unwrap();
-            if line.chars().next().unwrap() == 'L' {
-                Instruction::Left(num)
-            }
-            else {
-                Instruction::Right(num)
-            }
+    parse_instructions(&read_input(filename))
+        .unwrap_or_else(|e| panic!("Invalid input: {e}"))
+}
+
+fn parse_instructions(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    input.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.trim()
+                .parse::<Instruction>()
+                .map_err(|kind| ParseError { line: i + 1, kind })
         })
         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::rng::XorShift;
+
+    fn random_instructions(rng: &mut XorShift) -> Vec<Instruction> {
+        let length = rng.range(0, 50);
+        (0..length)
+            .map(|_| {
+                let n = rng.range(0, 1000) as isize;
+                if rng.bool() {
+                    Instruction::Left(n)
+                }
+                else {
+                    Instruction::Right(n)
+                }
+            })
+            .collect()
+    }
+
+    // Random instruction sequences through both the fast and the click-by-click dial
+    #[test]
+    fn rotate_matches_click_by_click() {
+        let mut rng = XorShift::new(2025);
+
+        for _ in 0..1000 {
+            let size = rng.range(1, 200) as isize;
+            let start = rng.range(0, size as u64) as isize;
+            let instructions = random_instructions(&mut rng);
+
+            let mut fast = Dial::new(size, start);
+            let mut slow = Dial::new(size, start);
+            for &instruction in &instructions {
+                let expected = slow.rotate_slow(instruction);
+                let actual = fast.rotate(instruction);
+                assert_eq!(
+                    (expected.end, expected.zero_crossings),
+                    (actual.end, actual.zero_crossings),
+                    "dial of size {size} starting at {start}, instructions {instructions:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_huge_amounts_without_overflow() {
+        let mut dial = Dial::new(100, 50);
+        let report = dial.rotate(Instruction::Right(isize::MAX));
+        // 9223372036854775807 clicks are 92233720368547758 full turns and 7 more
+        assert_eq!(report.end, 57);
+        assert_eq!(report.zero_crossings, 92233720368547758);
+    }
+
+    #[test]
+    fn direction_is_checked_before_the_amount() {
+        assert_eq!("X".parse::<Instruction>().unwrap_err(), InstructionError::UnknownDirection('X'));
+        assert_eq!("Xab".parse::<Instruction>().unwrap_err(), InstructionError::UnknownDirection('X'));
+        assert_eq!("L".parse::<Instruction>().unwrap_err(), InstructionError::MissingAmount);
+        assert_eq!("R1x".parse::<Instruction>().unwrap_err(), InstructionError::InvalidAmount(String::from("1x")));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
 
-use utils::read_input;
+use utils::{flag_value, has_flag, open_input};
 
 #[derive(Debug)]
 struct Range {
@@ -8,79 +9,262 @@ struct Range {
     end: usize,
 }
 
+#[derive(Debug)]
+enum RangeError {
+    Malformed(String),
+    Reversed { start: usize, end: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeError::Malformed(s) => write!(f, "malformed range {s:?}"),
+            RangeError::Reversed { start, end } => write!(f, "range start {start} is after end {end}"),
+            RangeError::Io(e) => write!(f, "failed to read input: {e}"),
+        }
+    }
+}
+
+impl Range {
+    fn from_str_radix(s: &str, base: usize) -> Result<Range, RangeError> {
+        let malformed = || RangeError::Malformed(s.to_string());
+        // Ids are bare digits of the base, `from_str_radix` on its own also takes a leading '+'
+        let number = |digits: &str| {
+            if !digits.chars().all(|c| c.is_digit(base as u32)) {
+                return Err(malformed());
+            }
+            usize::from_str_radix(digits, base as u32).map_err(|_| malformed())
+        };
+        let (start, end) = s.split_once("-").ok_or_else(malformed)?;
+        let start = number(start)?;
+        let end = number(end)?;
+
+        if start > end {
+            return Err(RangeError::Reversed { start, end });
+        }
+        Ok(Range { start, end })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeats {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(Debug, Clone)]
+struct RepeatRule {
+    repeats: Repeats,
+    // Allowed lengths of the repeated pattern, any length if None
+    pattern_lengths: Option<Vec<usize>>,
+    base: usize,
+}
+
+impl RepeatRule {
+    fn new(repeats: Repeats) -> RepeatRule {
+        RepeatRule { repeats, pattern_lengths: None, base: 10 }
+    }
+
+    fn with_pattern_lengths(mut self, lengths: Vec<usize>) -> RepeatRule {
+        self.pattern_lengths = Some(lengths);
+        self
+    }
+
+    fn with_base(mut self, base: usize) -> RepeatRule {
+        assert!((2..=36).contains(&base), "Base must be between 2 and 36");
+        self.base = base;
+        self
+    }
+
+    // Builds a rule from `--rule exact:N|min:N`, `--lengths a,b,..` and `--base N`
+    fn from_args() -> Option<RepeatRule> {
+        let Some(rule) = flag_value("--rule") else {
+            assert!(
+                !has_flag("--lengths") && !has_flag("--base"),
+                "--lengths and --base only apply together with --rule"
+            );
+            return None;
+        };
+        let (kind, count) = rule.split_once(":").expect("Rule should look like exact:N or min:N");
+        let count = count.parse::<usize>().expect("Could not parse repeat count");
+        // A pattern is always shorter than the id, so it repeats at least twice
+        assert!(count >= 2, "Repeat count must be at least 2");
+        let repeats = match kind {
+            "exact" => Repeats::Exactly(count),
+            "min" => Repeats::AtLeast(count),
+            _ => panic!("Unknown rule kind: {kind}"),
+        };
+
+        let mut rule = RepeatRule::new(repeats);
+        if let Some(lengths) = flag_value("--lengths") {
+            let lengths = lengths.split(",")
+                .map(|s| s.parse::<usize>().expect("Could not parse pattern length"))
+                .collect();
+            rule = rule.with_pattern_lengths(lengths);
+        }
+        if let Some(base) = flag_value("--base") {
+            rule = rule.with_base(base.parse().expect("Could not parse base"));
+        }
+        Some(rule)
+    }
+
+    fn allows(&self, pattern_len: usize, repeats: usize) -> bool {
+        let count_ok = match self.repeats {
+            Repeats::Exactly(n) => repeats == n,
+            Repeats::AtLeast(n) => repeats >= n,
+        };
+        let length_ok = self.pattern_lengths.as_ref()
+            .is_none_or(|lengths| lengths.contains(&pattern_len));
+        count_ok && length_ok
+    }
+
+    fn is_invalid(&self, id: usize) -> bool {
+        let digits = to_digits(id, self.base);
+        let len = digits.len();
+
+        (1..len)
+            .filter(|&pattern_len| len.is_multiple_of(pattern_len))
+            .filter(|&pattern_len| self.allows(pattern_len, len / pattern_len))
+            .any(|pattern_len| {
+                let pattern = &digits[..pattern_len];
+                digits.chunks(pattern_len).all(|chunk| chunk == pattern)
+            })
+    }
+}
+
 fn main() {
+    if let Some(rule) = RepeatRule::from_args() {
+        custom(&rule);
+        return;
+    }
     part_1();
     part_2();
 }
 
 fn part_1() {
-    let ranges = parse("inputs/day02pt1.txt");
-    let sum: usize = ranges.iter()
-        .map(|range| get_invalid_double_ids_from_range(range).iter().sum::<usize>())
-        .sum();
-    println!("Part 1: {:?}", sum);
+    let ranges = parse(open_input("inputs/day02pt1.txt"), 10);
+    let rule = RepeatRule::new(Repeats::Exactly(2));
+    match sum_invalid_ids(ranges, &rule) {
+        Ok(sum) => println!("Part 1: {:?}", sum),
+        Err(e) => eprintln!("Part 1: {e}"),
+    }
 }
 
 fn part_2() {
-    let ranges = parse("inputs/day02pt1.txt");
-    let sum: usize = ranges.iter()
-        .map(|range| get_all_invalid_ids_from_range(range).iter().sum::<usize>())
-        .sum();
-    println!("Part 2: {:?}", sum);
+    let ranges = parse(open_input("inputs/day02pt1.txt"), 10);
+    let rule = RepeatRule::new(Repeats::AtLeast(2));
+    match sum_invalid_ids(ranges, &rule) {
+        Ok(sum) => println!("Part 2: {:?}", sum),
+        Err(e) => eprintln!("Part 2: {e}"),
+    }
 }
 
-fn get_invalid_double_ids_from_range(range: &Range) -> Vec<usize> {
-    (range.start..=range.end)
-        .map(|i| i.to_string())
-        .filter(|s| {
-            let len = s.len();
-            len % 2 == 0 && s[0..len / 2] == s[len / 2..]
-        })
-        .map(|s| s.parse().unwrap())
-        .collect()
+fn custom(rule: &RepeatRule) {
+    let ranges = parse(open_input("inputs/day02pt1.txt"), rule.base);
+    match sum_invalid_ids(ranges, rule) {
+        Ok(sum) => println!("Custom {:?}: {:?}", rule, sum),
+        Err(e) => eprintln!("Custom {:?}: {e}", rule),
+    }
 }
 
-fn get_all_invalid_ids_from_range(range: &Range) -> Vec<usize> {
+// Stops at the first range that could not be read or parsed
+fn sum_invalid_ids(
+    ranges: impl Iterator<Item = Result<Range, RangeError>>,
+    rule: &RepeatRule,
+) -> Result<usize, RangeError> {
+    ranges
+        .map(|range| range.map(|range| invalid_ids(range, rule).sum::<usize>()))
+        .sum()
+}
+
+fn invalid_ids(range: Range, rule: &RepeatRule) -> impl Iterator<Item = usize> + '_ {
     (range.start..=range.end)
-        .map(|i| i.to_string())
-        .filter(|s| {
-            let mut unique_chars: HashMap<char, usize> = HashMap::new();
-            s.chars().
-                for_each(|c| {
-                    unique_chars.entry(c)
-                    .and_modify(|v| *v += 1)
-                    .or_insert(1);
-                });
-            let min_repeats = unique_chars.values().min().unwrap();
-
-            if *min_repeats == 1 {
-                return false
-            }
+        .filter(|&id| rule.is_invalid(id))
+}
 
-            // The code below finds anything based on chunks consisting of length s.len() / min_repeats
-            // This fails for edge cases where the min_repeat number is included twice into the
-            // duplicated part, e.g. 12211221. To include these, simply look at the old method
-            if s[0..s.len() / 2] == s[s.len() / 2..] {
-                return true;
-            }
+fn to_digits(mut n: usize, base: usize) -> Vec<usize> {
+    let mut digits = vec![n % base];
+    n /= base;
+    while n > 0 {
+        digits.push(n % base);
+        n /= base;
+    }
+    digits.reverse();
+    digits
+}
 
-            let chars = s.chars().collect::<Vec<char>>();
-            let chunks = chars.chunks(s.len() / min_repeats).collect::<Vec<&[char]>>();
-            chunks.iter()
-                .all(|ch| ch == &chunks[0])
+// Lazily yields one range per comma separated chunk, so the input is never held in memory at once.
+// Empty chunks, like between two commas, are malformed
+fn parse(reader: impl BufRead, base: usize) -> impl Iterator<Item = Result<Range, RangeError>> {
+    reader.split(b',')
+        .map(move |chunk| {
+            let chunk = chunk.map_err(RangeError::Io)?;
+            Range::from_str_radix(String::from_utf8_lossy(&chunk).trim(), base)
         })
-        .map(|s| s.parse().unwrap())
-        .collect()
-}
-
-fn parse(filename: &str) -> Vec<Range> {
-    read_input(filename)
-        .split(",")
-        .map(|s| {
-            let mut split = s.split("-");
-            let start = split.next().unwrap().parse::<usize>().unwrap();
-            let end = split.next().unwrap().parse::<usize>().unwrap();
-            Range { start, end }
-        })
-        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn parse_str(input: &str, base: usize) -> Vec<Result<Range, RangeError>> {
+        parse(Cursor::new(input.to_string()), base).collect()
+    }
+
+    #[test]
+    fn parses_ranges_up_to_the_trailing_newline() {
+        let ranges = parse_str("11-22,95-115\n", 10)
+            .into_iter()
+            .map(|range| range.map(|range| (range.start, range.end)).unwrap())
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(ranges, [(11, 22), (95, 115)]);
+    }
+
+    #[test]
+    fn rejects_reversed_ranges() {
+        let ranges = parse_str("1-2,5-3", 10);
+        assert!(ranges[0].is_ok());
+        assert!(matches!(ranges[1], Err(RangeError::Reversed { start: 5, end: 3 })));
+    }
+
+    #[test]
+    fn rejects_malformed_chunks() {
+        for chunk in ["abc", "1-", "-2", "1-2-3", "+1-2"] {
+            let ranges = parse_str(chunk, 10);
+            assert!(matches!(&ranges[0], Err(RangeError::Malformed(s)) if s == chunk), "{chunk}");
+        }
+    }
+
+    #[test]
+    fn rejects_empty_chunks() {
+        let ranges = parse_str("1-2,,3-4", 10);
+        assert_eq!(ranges.len(), 3);
+        assert!(matches!(&ranges[1], Err(RangeError::Malformed(s)) if s.is_empty()));
+    }
+
+    #[test]
+    fn sum_stops_at_the_first_error() {
+        let rule = RepeatRule::new(Repeats::Exactly(2));
+        let ranges = parse(Cursor::new("11-22,5-3"), 10);
+        assert!(matches!(sum_invalid_ids(ranges, &rule), Err(RangeError::Reversed { .. })));
+
+        let ranges = parse(Cursor::new("11-22,95-115\n"), 10);
+        assert_eq!(sum_invalid_ids(ranges, &rule).unwrap(), 11 + 22 + 99);
+    }
+
+    #[test]
+    fn repeats_in_base_16() {
+        let rule = RepeatRule::new(Repeats::Exactly(2)).with_base(16);
+        assert!(rule.is_invalid(0xabab));
+        assert!(!rule.is_invalid(0xabac));
+        // 0x1111 repeats "1" four times but "11" exactly twice
+        assert!(rule.is_invalid(0x1111));
+        assert!(!rule.is_invalid(0x111));
+
+        // aa, bb, .., ff
+        let ranges = parse(Cursor::new("a0-ff"), 16);
+        assert_eq!(sum_invalid_ids(ranges, &rule).unwrap(), 0xaa + 0xbb + 0xcc + 0xdd + 0xee + 0xff);
+    }
 }
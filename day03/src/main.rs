@@ -1,40 +1,136 @@
-use utils::read_lines;
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use utils::{flag_value, has_flag, read_lines};
+
+#[derive(Debug, PartialEq)]
+struct Selection {
+    indices: Vec<usize>,
+    value: BigUint,
+}
+
+#[derive(Debug)]
+struct BankTooShort {
+    bank: usize,
+    len: usize,
+    digits: usize,
+}
+
+impl fmt::Display for BankTooShort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bank {} has only {} batteries, cannot select {} digits", self.bank, self.len, self.digits)
+    }
+}
 
 fn main() {
+    if let Some(digits) = flag_value("--digits") {
+        custom(digits.parse().expect("Could not parse number of digits"));
+        return;
+    }
     part_1();
     part_2();
 }
 
 fn part_1() {
     let banks = parse("inputs/day03pt1.txt");
-    let rating = find_banks_joltage(&banks, 2);
-    println!("Part 1: {:?}", rating);
+    match find_banks_joltage(&banks, 2) {
+        Ok(rating) => {
+            if has_flag("--explain") {
+                explain_banks(&banks, 2);
+            }
+            println!("Part 1: {:?}", rating);
+        },
+        Err(e) => eprintln!("Part 1: {e}"),
+    }
 }
 
 fn part_2() {
     let banks = parse("inputs/day03pt1.txt");
-    let rating = find_banks_joltage(&banks, 12);
-    println!("Part 2: {:?}", rating);
+    match find_banks_joltage(&banks, 12) {
+        Ok(rating) => {
+            if has_flag("--explain") {
+                explain_banks(&banks, 12);
+            }
+            println!("Part 2: {:?}", rating);
+        },
+        Err(e) => eprintln!("Part 2: {e}"),
+    }
+}
+
+fn custom(digits: usize) {
+    let banks = parse("inputs/day03pt1.txt");
+    match find_banks_joltage(&banks, digits) {
+        Ok(rating) => {
+            if has_flag("--explain") {
+                explain_banks(&banks, digits);
+            }
+            println!("{digits} digits: {:?}", rating);
+        },
+        Err(e) => eprintln!("Error: {e}"),
+    }
 }
 
-fn find_banks_joltage(banks: &[Vec<u32>], digits: usize) -> usize {
+fn find_banks_joltage(banks: &[Vec<u32>], digits: usize) -> Result<BigUint, BankTooShort> {
     banks.iter()
-        .map(|bank| {
-            let mut start = 0;
-            (0..digits).rev()
-                .map(|digit| {
-                    let result = recursive_maximum_in_slice(&bank[start..], digit);
-                    start += result.0 + 1;
-                    *result.1 as usize * 10_usize.pow(digit as u32)
-                })
-                .sum::<usize>()})
-            .sum()
+        .enumerate()
+        .map(|(i, bank)| {
+            if bank.len() < digits {
+                return Err(BankTooShort { bank: i, len: bank.len(), digits });
+            }
+            Ok(select_largest_subsequence(bank, digits).value)
+        })
+        .sum()
 }
 
-fn recursive_maximum_in_slice(bank: &[u32], stop: usize) -> (usize, &u32) {
-    let end_pos = bank.len() - stop;
-    let max = bank[..end_pos].iter().max().unwrap();
-    bank[..end_pos].iter().enumerate().find(|(_, v)| v == &max).unwrap()
+// Prints every bank with the selected batteries highlighted, in bold green or
+// between brackets when `--no-color` is passed
+fn explain_banks(banks: &[Vec<u32>], digits: usize) {
+    let color = !has_flag("--no-color");
+
+    for (i, bank) in banks.iter().enumerate() {
+        let selection = select_largest_subsequence(bank, digits);
+        let mut selected = selection.indices.iter().peekable();
+        let rendered = bank.iter()
+            .enumerate()
+            .map(|(j, joltage)| {
+                if selected.next_if_eq(&&j).is_none() {
+                    joltage.to_string()
+                }
+                else if color {
+                    format!("\x1b[1;32m{joltage}\x1b[0m")
+                }
+                else {
+                    format!("[{joltage}]")
+                }
+            })
+            .collect::<String>();
+        println!("Bank {i}: {rendered} -> {}", selection.value);
+    }
+}
+
+fn to_number(digits: impl Iterator<Item = u32>) -> BigUint {
+    digits.fold(BigUint::ZERO, |value, digit| value * 10_u32 + digit)
+}
+
+// Largest subsequence of `digits` batteries in O(n): a digit pops every smaller digit before it
+// off the stack as long as enough batteries remain to still fill all positions.
+// `digits` must not exceed the bank length
+fn select_largest_subsequence(bank: &[u32], digits: usize) -> Selection {
+    let mut stack: Vec<usize> = Vec::with_capacity(bank.len());
+    let mut drops = bank.len() - digits;
+
+    for (i, &joltage) in bank.iter().enumerate() {
+        while drops > 0 && stack.last().is_some_and(|&top| bank[top] < joltage) {
+            stack.pop();
+            drops -= 1;
+        }
+        stack.push(i);
+    }
+    stack.truncate(digits);
+
+    let value = to_number(stack.iter().map(|&i| bank[i]));
+    Selection { indices: stack, value }
 }
 
 fn parse(filename: &str) -> Vec<Vec<u32>> {
@@ -44,4 +140,50 @@ fn parse(filename: &str) -> Vec<Vec<u32>> {
             .map(|c| c.to_digit(10).unwrap())
             .collect::<Vec<u32>>())
         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::rng::XorShift;
+
+    // Reference implementation which rescans the remaining slice for every digit, O(n * digits)
+    fn select_by_scanning(bank: &[u32], digits: usize) -> Selection {
+        let mut start = 0;
+        let mut indices = Vec::with_capacity(digits);
+        let value = to_number((0..digits).rev()
+            .map(|digit| {
+                let result = recursive_maximum_in_slice(&bank[start..], digit);
+                indices.push(start + result.0);
+                start += result.0 + 1;
+                *result.1
+            }));
+        Selection { indices, value }
+    }
+
+    fn recursive_maximum_in_slice(bank: &[u32], stop: usize) -> (usize, &u32) {
+        let end_pos = bank.len() - stop;
+        let max = bank[..end_pos].iter().max().unwrap();
+        bank[..end_pos].iter().enumerate().find(|(_, v)| v == &max).unwrap()
+    }
+
+    // The stack based selection against the slice scanning one on random banks
+    #[test]
+    fn stack_matches_scanning() {
+        let mut rng = XorShift::new(2025);
+
+        for _ in 0..1000 {
+            let len = rng.range(1, 100) as usize;
+            let bank = (0..len)
+                .map(|_| rng.range(0, 10) as u32)
+                .collect::<Vec<u32>>();
+            let digits = rng.range(1, len as u64 + 1) as usize;
+
+            assert_eq!(
+                select_largest_subsequence(&bank, digits),
+                select_by_scanning(&bank, digits),
+                "selecting {digits} digits from {bank:?}"
+            );
+        }
+    }
 }
\ No newline at end of file
@@ -1,93 +1,207 @@
-use std::collections::HashMap;
-
-use utils::read_lines;
+use utils::viz::{Cell, Color, Frame, Visualizer};
+use utils::{flag_value, has_flag, read_lines};
 
 const NEIGHBOR_STEPS: [(isize, isize);8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (1, -1), (-1, -1)];
 
+#[derive(Debug, Clone, Copy)]
+enum Neighborhood {
+    Four,
+    Eight,
+}
 
-fn main() {
-    part_1();
-    part_2();
+impl Neighborhood {
+    fn steps(&self) -> &'static [(isize, isize)] {
+        match self {
+            // The orthogonal steps come first in NEIGHBOR_STEPS
+            Neighborhood::Four => &NEIGHBOR_STEPS[..4],
+            Neighborhood::Eight => &NEIGHBOR_STEPS,
+        }
+    }
 }
 
-fn part_1() {
-    let (papers, (width, height)) = parse_paper_locations("inputs/day04pt1.txt");
-    let count: usize = papers.iter()
-        .map(|p| get_neighbors(*p, &papers, width, height).len())
-        .filter(|n| *n < 4)
-        .count();
-    println!("Part 1: {:?}", count);
+#[derive(Debug)]
+struct RemovalRules {
+    // A roll is accessible when it has fewer neighbors than this
+    threshold: u8,
+    neighborhood: Neighborhood,
 }
 
-fn part_2() {
-    let (papers, (width, height)) = parse_paper_locations("inputs/day04pt1.txt");
-    let neighbors = get_neighbor_map(&papers, width, height);
-    let count = recursive_remove_neighbors(neighbors);
-    println!("Part 2: {:?}", count);
+impl RemovalRules {
+    fn from_args() -> RemovalRules {
+        let threshold = flag_value("--threshold")
+            .map(|s| s.parse::<u8>().expect("Could not parse threshold"))
+            .unwrap_or(4);
+        let neighborhood = if has_flag("--four-way") { Neighborhood::Four } else { Neighborhood::Eight };
+        RemovalRules { threshold, neighborhood }
+    }
 }
 
-fn get_neighbor_map(papers: &[(isize, isize)], width: isize, height: isize) -> HashMap<(isize, isize), Vec<(isize, isize)>> {
-    let mut map = HashMap::new();
-    papers.iter()
-        .for_each(|&pos| {
-            let neighbors = get_neighbors(pos, papers, width, height);
-            map.insert(pos, neighbors);
-        });
-    map
+#[derive(Debug)]
+struct RoundReport {
+    round: usize,
+    removed: usize,
+    remaining: usize,
+}
+
+// Dense bitmap of paper roll positions, one bit per cell in row-major order
+struct Grid {
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
 }
 
-fn recursive_remove_neighbors(mut neighbors: HashMap<(isize, isize), Vec<(isize, isize)>>) -> usize {
-    let mut count = 0;
+impl Grid {
+    fn new(width: usize, height: usize) -> Grid {
+        Grid { width, height, bits: vec![0; (width * height).div_ceil(64)] }
+    }
 
-    let remove = neighbors.iter()
-        .filter(|(_, neighbors_vec)| neighbors_vec.len() < 4)
-        .map(|(&pos, _)| pos)
-        .collect::<Vec<_>>();
+    fn contains(&self, idx: usize) -> bool {
+        self.bits[idx / 64] & (1 << (idx % 64)) != 0
+    }
 
-    if remove.is_empty() {
-        return count;
+    fn insert(&mut self, idx: usize) {
+        self.bits[idx / 64] |= 1 << (idx % 64);
     }
 
-    count += remove.len();
+    fn remove(&mut self, idx: usize) {
+        self.bits[idx / 64] &= !(1 << (idx % 64));
+    }
 
-    remove.iter()
-        .for_each(|x| {neighbors.remove(x); });
+    fn rolls(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.width * self.height).filter(|&idx| self.contains(idx))
+    }
 
-    neighbors.iter_mut()
-        .for_each(|(_, neighbor_vec)| {
-            neighbor_vec.retain(|x| !remove.contains(x));
-        });
+    fn neighbors(&self, idx: usize, neighborhood: Neighborhood) -> impl Iterator<Item = usize> + '_ {
+        let (row, col) = ((idx / self.width) as isize, (idx % self.width) as isize);
+        neighborhood.steps().iter()
+            .map(move |(drow, dcol)| (row + drow, col + dcol))
+            .filter(|&(r, c)| r >= 0 && r < self.height as isize && c >= 0 && c < self.width as isize)
+            .map(|(r, c)| r as usize * self.width + c as usize)
+    }
+
+    fn neighbor_counts(&self, neighborhood: Neighborhood) -> Vec<u8> {
+        let mut counts = vec![0; self.width * self.height];
+        self.rolls()
+            .for_each(|idx| {
+                counts[idx] = self.neighbors(idx, neighborhood).filter(|&n| self.contains(n)).count() as u8;
+            });
+        counts
+    }
+
+    fn render(&self) -> String {
+        (0..self.height)
+            .map(|row| {
+                (0..self.width)
+                    .map(|col| if self.contains(row * self.width + col) { '@' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Remaining rolls in green, the rolls removed in the last round as red crosses
+    fn to_frame(&self, removed: &[usize]) -> Frame {
+        let mut frame = Frame::new(self.width, self.height);
+        self.rolls()
+            .for_each(|idx| frame.set(idx / self.width, idx % self.width, Cell::new('@', Color::Green)));
+        removed.iter()
+            .for_each(|&idx| frame.set(idx / self.width, idx % self.width, Cell::new('x', Color::Red)));
+        frame
+    }
+}
+
+fn main() {
+    part_1();
+    part_2();
+}
 
-    count + recursive_remove_neighbors(neighbors)
+fn part_1() {
+    let grid = parse_paper_locations("inputs/day04pt1.txt");
+    let rules = RemovalRules::from_args();
+    let counts = grid.neighbor_counts(rules.neighborhood);
+    let count = grid.rolls()
+        .filter(|&idx| counts[idx] < rules.threshold)
+        .count();
+    println!("Part 1: {:?}", count);
 }
 
-fn get_neighbors(position: (isize, isize), papers: &[(isize, isize)], width: isize, height: isize) -> Vec<(isize, isize)> {
-    let neighbors = get_valid_neighbors(position, width, height);
-    neighbors.iter()
-        .filter(|neighbor| papers.contains(neighbor))
-        .cloned()
-        .collect()
+fn part_2() {
+    let mut grid = parse_paper_locations("inputs/day04pt1.txt");
+    let rules = RemovalRules::from_args();
+    let show_rounds = has_flag("--rounds");
+    let render = has_flag("--render");
+    let mut visualizer = Visualizer::from_args();
+
+    if let Some(visualizer) = visualizer.as_mut() {
+        visualizer.show(&grid.to_frame(&[])).expect("Failed to show frame");
+    }
+    let reports = remove_in_rounds(&mut grid, &rules, |report, removed, grid| {
+        if let Some(visualizer) = visualizer.as_mut() {
+            visualizer.show(&grid.to_frame(removed)).expect("Failed to show frame");
+        }
+        if show_rounds || render {
+            println!("Round {}: removed {}, remaining {}", report.round, report.removed, report.remaining);
+        }
+        if render {
+            println!("{}\n", grid.render());
+        }
+    });
+    let count: usize = reports.iter().map(|report| report.removed).sum();
+    println!("Part 2: {:?}", count);
 }
 
-fn get_valid_neighbors(position: (isize, isize), width: isize, height: isize) -> Vec<(isize, isize)> {
-    NEIGHBOR_STEPS.iter()
-        .map(|(drow, dcol)| (position.0 + drow, position.1 + dcol))
-        .filter(|&(x, y)| x >= 0 && x < height && y >= 0 && y < width)
-        .collect()
+// Removes all accessible rolls round by round until none are left, calling `on_round` with the
+// removed cells after each round.
+// Only rolls whose count just dropped below the threshold are checked in the next round,
+// so every roll is looked at a constant number of times
+fn remove_in_rounds(
+    grid: &mut Grid,
+    rules: &RemovalRules,
+    mut on_round: impl FnMut(&RoundReport, &[usize], &Grid),
+) -> Vec<RoundReport> {
+    let mut counts = grid.neighbor_counts(rules.neighborhood);
+    let mut remaining = grid.rolls().count();
+    let mut reports = vec![];
+    let mut frontier = grid.rolls()
+        .filter(|&idx| counts[idx] < rules.threshold)
+        .collect::<Vec<usize>>();
+
+    while !frontier.is_empty() {
+        frontier.iter().for_each(|&idx| grid.remove(idx));
+
+        let mut next = vec![];
+        for &idx in &frontier {
+            for neighbor in grid.neighbors(idx, rules.neighborhood) {
+                if grid.contains(neighbor) {
+                    counts[neighbor] -= 1;
+                    if counts[neighbor] + 1 == rules.threshold {
+                        next.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        remaining -= frontier.len();
+        let report = RoundReport { round: reports.len() + 1, removed: frontier.len(), remaining };
+        on_round(&report, &frontier, grid);
+        reports.push(report);
+        frontier = next;
+    }
+    reports
 }
 
-fn parse_paper_locations(filename: &str) -> (Vec<(isize, isize)>, (isize, isize)) {
-    let mut paper_locations: Vec<(isize, isize)> = Vec::new();
+fn parse_paper_locations(filename: &str) -> Grid {
     let lines = read_lines(filename);
     let height = lines.len();
     let width = lines[0].len();
+    let mut grid = Grid::new(width, height);
 
     lines.into_iter()
         .enumerate()
         .for_each(|(row_no, line)| {
             line.chars()
                 .enumerate()
-                .for_each(|(col_no, c)| if c == '@' { paper_locations.push((row_no as isize, col_no as isize)) })
+                .for_each(|(col_no, c)| if c == '@' { grid.insert(row_no * width + col_no) })
         });
-    (paper_locations, (width as isize, height as isize))
+    grid
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
-use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
 
-use utils::read_input;
+use utils::interval::IntervalSet;
+use utils::{flag_value, has_flag, read_input};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 struct IdRange {
@@ -8,22 +10,39 @@ struct IdRange {
     end: usize,
 }
 
-impl PartialOrd for IdRange {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+#[derive(Debug)]
+struct IdLookup {
+    id: usize,
+    // Merged range containing the id, None if the id is spoiled
+    range: Option<IdRange>,
+}
+
+#[derive(Debug)]
+enum InputError {
+    MalformedRange { line: usize, text: String },
+    ReversedRange { line: usize, start: usize, end: usize },
+    MalformedId { line: usize, text: String },
 }
 
-impl Ord for IdRange {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.start.cmp(&other.start)
-            .then_with(|| self.end.cmp(&other.end))
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::MalformedRange { line, text } => write!(f, "line {line}: malformed range {text:?}"),
+            InputError::ReversedRange { line, start, end } => write!(f, "line {line}: range start {start} is after end {end}"),
+            InputError::MalformedId { line, text } => write!(f, "line {line}: malformed id {text:?}"),
+        }
     }
 }
 
 fn main() {
     part_1();
     part_2();
+    if has_flag("--merged") {
+        let (ranges, _) = parse_ranges_and_ids("inputs/day05pt1.txt");
+        build_id_set(&ranges, has_flag("--adjacent-merge"))
+            .iter()
+            .for_each(|range| println!("{}-{}", range.start(), range.end()));
+    }
 }
 
 fn part_1() {
@@ -39,61 +58,146 @@ fn part_2() {
 }
 
 fn check_valid_ids(ranges: &[IdRange], ids: &[usize]) -> usize {
-    // Inclusive range
-    ids.iter()
-        .filter(|id| {
-            ranges.iter().any(|range| id >= &&range.start && id <= &&range.end)
-        })
+    let lookups = lookup_ids(&build_id_set(ranges, has_flag("--adjacent-merge")), ids);
+    if let Some(filename) = flag_value("--csv") {
+        write_lookups_csv(&filename, &lookups);
+    }
+    lookups.iter()
+        .filter(|lookup| lookup.range.is_some())
         .count()
 }
 
-fn count_all_valid_ids(ranges: &[IdRange]) -> usize {
-    let merged = merge_ranges(ranges);
-    merged.iter()
-        .map(|range| range.end - range.start + 1)
-        .sum()
+// Sorts the ids once and sweeps them against the sorted, merged ranges of the set,
+// O(ids log ids + ranges). The lookups are returned in ascending id order
+fn lookup_ids(fresh: &IntervalSet<usize>, ids: &[usize]) -> Vec<IdLookup> {
+    let mut sorted = ids.to_vec();
+    sorted.sort();
+
+    let mut ranges = fresh.iter().peekable();
+    sorted.into_iter()
+        .map(|id| {
+            while ranges.next_if(|range| *range.end() < id).is_some() {}
+            let range = ranges.peek()
+                .filter(|range| *range.start() <= id)
+                .map(|range| IdRange { start: *range.start(), end: *range.end() });
+            IdLookup { id, range }
+        })
+        .collect()
+}
+
+fn write_lookups_csv(filename: &str, lookups: &[IdLookup]) {
+    let mut csv = String::from("id,fresh,range_start,range_end\n");
+    for lookup in lookups {
+        match lookup.range {
+            Some(range) => csv.push_str(&format!("{},true,{},{}\n", lookup.id, range.start, range.end)),
+            None => csv.push_str(&format!("{},false,,\n", lookup.id)),
+        }
+    }
+    fs::write(filename, csv).expect("Failed to write csv file");
+}
+
+fn count_all_valid_ids(ranges: &[IdRange]) -> u128 {
+    build_id_set(ranges, has_flag("--adjacent-merge")).covered_len()
+}
+
+// Touching ranges like 3-5 and 6-8 cover the same ids either way, they are only combined
+// into 3-8 with `adjacent_merge`
+fn build_id_set(ranges: &[IdRange], adjacent_merge: bool) -> IntervalSet<usize> {
+    // Inclusive ranges
+    let ranges = ranges.iter().map(|range| range.start..=range.end);
+    IntervalSet::from_ranges(ranges, adjacent_merge)
+}
+
+fn parse_ranges_and_ids(filename: &str) -> (Vec<IdRange>, Vec<usize>) {
+    parse_input(&read_input(filename))
+        .unwrap_or_else(|e| panic!("Invalid input: {e}"))
 }
 
-fn merge_ranges(ranges: &[IdRange]) -> Vec<IdRange> {
-    let mut old: Vec<IdRange> = ranges.to_vec();
-    let mut new : Vec<IdRange> = Vec::new();
+// Ranges and ids are separated by a blank line, either section may be empty
+fn parse_input(input: &str) -> Result<(Vec<IdRange>, Vec<usize>), InputError> {
+    let mut ranges = vec![];
+    let mut ids = vec![];
+    let mut in_ranges = true;
 
-    old.sort();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        let line_no = i + 1;
 
-    let mut current = old[0];
+        if line.is_empty() {
+            in_ranges = false;
+            continue;
+        }
 
-    for &next in old.iter().skip(1) {
-        if current.end >= next.start {
-            current.end = current.end.max(next.end);
+        if in_ranges {
+            let malformed = || InputError::MalformedRange { line: line_no, text: line.to_string() };
+            let (start, end) = line.split_once("-").ok_or_else(malformed)?;
+            let start = start.parse::<usize>().map_err(|_| malformed())?;
+            let end = end.parse::<usize>().map_err(|_| malformed())?;
+            if start > end {
+                return Err(InputError::ReversedRange { line: line_no, start, end });
+            }
+            ranges.push(IdRange { start, end });
         }
         else {
-            new.push(current);
-            current = next;
+            let id = line.parse::<usize>()
+                .map_err(|_| InputError::MalformedId { line: line_no, text: line.to_string() })?;
+            ids.push(id);
         }
     }
-    new.push(current);
 
-    new
+    Ok((ranges, ids))
 }
 
-fn parse_ranges_and_ids(filename: &str) -> (Vec<IdRange>, Vec<usize>) {
-    let input = read_input(filename);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut ranges = vec![];
-    let mut ids = vec![];
+    fn r(start: usize, end: usize) -> IdRange {
+        IdRange { start, end }
+    }
 
-    let split = input.split("\r\n\r\n").collect::<Vec<&str>>();
-    split[0].split("\r\n")
-        .for_each(|line| {
-            let numbers: Vec<&str> = line.trim().split("-").collect();
-            let range = IdRange { start : numbers[0].parse().unwrap(), end: numbers[1].parse().unwrap() };
-            ranges.push(range);
-        });
+    // Merges `ranges` in both modes and checks the stored ranges and the number of covered ids
+    fn check_merge(ranges: &[IdRange], separate: &[IdRange], adjacent: &[IdRange]) {
+        for (adjacent_merge, expected) in [(false, separate), (true, adjacent)] {
+            let set = build_id_set(ranges, adjacent_merge);
+            let merged = set.iter()
+                .map(|range| r(*range.start(), *range.end()))
+                .collect::<Vec<IdRange>>();
+            assert_eq!(merged, expected, "adjacent merge {adjacent_merge}");
+
+            let covered: usize = expected.iter().map(|range| range.end - range.start + 1).sum();
+            assert_eq!(set.covered_len(), covered as u128, "adjacent merge {adjacent_merge}");
+        }
+    }
 
-    split[1].split("\r\n")
-        .for_each(|line| {
-            ids.push(line.trim().parse::<usize>().unwrap());
-        });
+    #[test]
+    fn merge_empty() {
+        check_merge(&[], &[], &[]);
+    }
 
-    (ranges, ids)
+    #[test]
+    fn merge_nested() {
+        check_merge(&[r(1, 10), r(3, 5)], &[r(1, 10)], &[r(1, 10)]);
+    }
+
+    #[test]
+    fn merge_touching() {
+        check_merge(&[r(6, 8), r(3, 5)], &[r(3, 5), r(6, 8)], &[r(3, 8)]);
+    }
+
+    #[test]
+    fn merge_overlapping() {
+        check_merge(&[r(3, 6), r(5, 8)], &[r(3, 8)], &[r(3, 8)]);
+    }
+
+    #[test]
+    fn merge_identical() {
+        check_merge(&[r(4, 7), r(4, 7)], &[r(4, 7)], &[r(4, 7)]);
+    }
+
+    #[test]
+    fn merge_disjoint() {
+        let disjoint = [r(1, 2), r(5, 5), r(10, 12)];
+        check_merge(&[r(10, 12), r(1, 2), r(5, 5)], &disjoint, &disjoint);
+    }
 }
\ No newline at end of file
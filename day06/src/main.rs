@@ -1,116 +1,364 @@
-use regex::Regex;
+use std::fmt;
 
-use utils::read_lines;
+use num_bigint::BigInt;
 
-fn main() {
-    part_1();
-    part_2();
+use utils::{has_flag, read_lines};
+
+// Arithmetic used to evaluate the worksheet, checked for machine integers and exact for big integers
+trait Number: Sized + fmt::Debug {
+    fn from_usize(n: usize) -> Self;
+    // `digits` only contains ASCII digits
+    fn from_digits(digits: &str) -> Result<Self, ErrorKind>;
+    fn add(self, other: Self) -> Result<Self, ErrorKind>;
+    fn sub(self, other: Self) -> Result<Self, ErrorKind>;
+    fn mul(self, other: Self) -> Result<Self, ErrorKind>;
+    fn div(self, other: Self) -> Result<Self, ErrorKind>;
+    fn pow(self, exponent: Self) -> Result<Self, ErrorKind>;
 }
 
-fn part_1() {
-    let (numbers, operands) = parse("inputs/day06pt1.txt");
-    let sum = get_sum_calculations(&numbers, &operands);
-    println!("Part 1: {:?}", sum);
+impl Number for usize {
+    fn from_usize(n: usize) -> Self {
+        n
+    }
+
+    fn from_digits(digits: &str) -> Result<Self, ErrorKind> {
+        digits.parse().map_err(|_| ErrorKind::Overflow)
+    }
+
+    fn add(self, other: Self) -> Result<Self, ErrorKind> {
+        self.checked_add(other).ok_or(ErrorKind::Overflow)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, ErrorKind> {
+        self.checked_sub(other).ok_or(ErrorKind::Negative)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, ErrorKind> {
+        self.checked_mul(other).ok_or(ErrorKind::Overflow)
+    }
+
+    fn div(self, other: Self) -> Result<Self, ErrorKind> {
+        self.checked_div(other).ok_or(ErrorKind::DivisionByZero)
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self, ErrorKind> {
+        let exponent = u32::try_from(exponent).map_err(|_| ErrorKind::Overflow)?;
+        self.checked_pow(exponent).ok_or(ErrorKind::Overflow)
+    }
 }
 
-fn part_2() {
-    let (numbers_str, operands) = parse_numbers_as_string_and_operands("inputs/day06pt1.txt");
-    let sum = get_sum_right_to_left_order(&numbers_str, &operands);
-    println!("Part 2: {:?}", sum);
+impl Number for BigInt {
+    fn from_usize(n: usize) -> Self {
+        BigInt::from(n)
+    }
+
+    fn from_digits(digits: &str) -> Result<Self, ErrorKind> {
+        digits.parse().map_err(|_| ErrorKind::InvalidNumber(digits.to_string()))
+    }
+
+    fn add(self, other: Self) -> Result<Self, ErrorKind> {
+        Ok(self + other)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, ErrorKind> {
+        Ok(self - other)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, ErrorKind> {
+        Ok(self * other)
+    }
+
+    fn div(self, other: Self) -> Result<Self, ErrorKind> {
+        if other == BigInt::ZERO {
+            return Err(ErrorKind::DivisionByZero);
+        }
+        Ok(self / other)
+    }
+
+    // Exponents beyond u32 would not fit in memory anyway
+    fn pow(self, exponent: Self) -> Result<Self, ErrorKind> {
+        let exponent = u32::try_from(&exponent).map_err(|_| ErrorKind::InvalidExponent)?;
+        Ok(BigInt::pow(&self, exponent))
+    }
 }
 
-fn get_sum_right_to_left_order(numbers: &[String], operands: &[u8]) -> usize {
-    let mut calculations = vec![];
-    let mut operand_idx = 0;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
 
-    // Right-to-left order so flip both Vecs
-    let operands = operands.iter().rev().collect::<Vec<_>>();
-    let numbers = numbers.iter()
-        .map(|s| s.chars().rev().collect::<String>())
-        .collect::<Vec<_>>();
+impl Op {
+    fn symbol(&self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+            Op::Pow => '^',
+        }
+    }
 
-    // Initialize to 1, subtract 1 in case of addition
-    let mut value = 1;
+    fn from_char(c: char) -> Option<Op> {
+        match c {
+            '+' => Some(Op::Add),
+            '-' => Some(Op::Sub),
+            '*' => Some(Op::Mul),
+            '/' => Some(Op::Div),
+            '^' => Some(Op::Pow),
+            _ => None,
+        }
+    }
 
-    for i in 0..numbers[0].len() {
-        let mut value_str = String::new();
-        for row in &numbers {
-            value_str.push(row.chars().nth(i).unwrap());
+    // Only addition and multiplication have an identity, so only they accept a block without numbers
+    fn identity(&self) -> Option<usize> {
+        match self {
+            Op::Add => Some(0),
+            Op::Mul => Some(1),
+            Op::Sub | Op::Div | Op::Pow => None,
         }
-        if !value_str.trim().is_empty() {
-            let new = value_str.trim().parse::<usize>().unwrap();
-            match operands[operand_idx] {
-                1 => value *= new,
-                _ => value += new,
-            }
-            if i == numbers[0].len() - 1 {
-                if *operands[operand_idx] == 0 {
-                    value -= 1;
-                }
-                calculations.push(value);
-            }
+    }
+
+    fn apply<N: Number>(&self, a: N, b: N) -> Result<N, ErrorKind> {
+        match self {
+            Op::Add => a.add(b),
+            Op::Sub => a.sub(b),
+            Op::Mul => a.mul(b),
+            Op::Div => a.div(b),
+            Op::Pow => a.pow(b),
+        }
+    }
+
+    // Subtraction and division fold left to right, powers are right associative like 2 ^ 3 ^ 2
+    fn evaluate<N: Number>(&self, numbers: &[String]) -> Result<N, ErrorKind> {
+        let mut values = numbers.iter().map(|digits| to_number::<N>(digits));
+
+        if let Some(identity) = self.identity() {
+            return values.try_fold(N::from_usize(identity), |acc, n| self.apply(acc, n?));
+        }
+        match self {
+            Op::Pow => {
+                let mut values = values.rev();
+                let last = values.next().ok_or(ErrorKind::NoNumbers)??;
+                values.try_fold(last, |acc, n| self.apply(n?, acc))
+            },
+            _ => {
+                let first = values.next().ok_or(ErrorKind::NoNumbers)??;
+                values.try_fold(first, |acc, n| self.apply(acc, n?))
+            },
         }
-        else {
-            if *operands[operand_idx] == 0 {
-                value -= 1;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ErrorKind {
+    UnknownOperator(char),
+    MissingOperator,
+    InvalidNumber(String),
+    NoNumbers,
+    DivisionByZero,
+    Overflow,
+    Negative,
+    InvalidExponent,
+    // Adding this problem's answer overflowed the grand total
+    TotalOverflow,
+}
+
+#[derive(Debug)]
+struct WorksheetError {
+    // Index of the problem block, counted from the left
+    problem: usize,
+    kind: ErrorKind,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOperator(c) => write!(f, "unknown operator {c:?}"),
+            ErrorKind::MissingOperator => write!(f, "missing operator"),
+            ErrorKind::InvalidNumber(s) => write!(f, "invalid number {s:?}"),
+            ErrorKind::NoNumbers => write!(f, "no numbers to apply the operator to"),
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::Overflow => write!(f, "result does not fit in {} bits, try --big", usize::BITS),
+            ErrorKind::Negative => write!(f, "result is negative, try --big"),
+            ErrorKind::InvalidExponent => write!(f, "exponent does not fit in 32 bits"),
+            ErrorKind::TotalOverflow => write!(f, "grand total does not fit in {} bits, try --big", usize::BITS),
+        }
+    }
+}
+
+impl fmt::Display for WorksheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "problem {}: {}", self.problem, self.kind)
+    }
+}
+
+// One problem of the worksheet: the digit rows between two blank columns and the operator below them
+#[derive(Debug)]
+struct Problem {
+    rows: Vec<Vec<char>>,
+    op: Op,
+}
+
+impl Problem {
+    // Every row is one number, read left to right
+    fn read_rows(&self) -> Vec<String> {
+        self.rows.iter()
+            .filter_map(|row| to_digits(row.iter()))
+            .collect()
+    }
+
+    // Cephalopod math: every column is one number, read top to bottom, starting with the rightmost column
+    fn read_columns_right_to_left(&self) -> Vec<String> {
+        let width = self.rows.first().map_or(0, |row| row.len());
+        (0..width).rev()
+            .filter_map(|col| to_digits(self.rows.iter().map(|row| &row[col])))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Worksheet {
+    problems: Vec<Problem>,
+}
+
+impl Worksheet {
+    fn from_lines(lines: &[String]) -> Result<Worksheet, WorksheetError> {
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        // Lines can be cut short when they end in spaces, pad them to a full matrix
+        let matrix: Vec<Vec<char>> = lines.iter()
+            .map(|line| {
+                let mut row = line.chars().collect::<Vec<char>>();
+                row.resize(width, ' ');
+                row
+            })
+            .collect();
+        let Some((operators, digits)) = matrix.split_last() else {
+            return Ok(Worksheet { problems: vec![] });
+        };
+
+        let blank_columns = (0..width)
+            .filter(|&col| matrix.iter().all(|row| row[col] == ' '))
+            .chain([width]);
+
+        let mut problems = vec![];
+        let mut start = 0;
+        for end in blank_columns {
+            if end > start {
+                let error = |kind| WorksheetError { problem: problems.len(), kind };
+                let c = *operators[start..end].iter()
+                    .find(|c| **c != ' ')
+                    .ok_or_else(|| error(ErrorKind::MissingOperator))?;
+                let op = Op::from_char(c).ok_or_else(|| error(ErrorKind::UnknownOperator(c)))?;
+                let rows = digits.iter()
+                    .map(|row| row[start..end].to_vec())
+                    .collect();
+                problems.push(Problem { rows, op });
             }
-            calculations.push(value);
-            operand_idx += 1;
-            value = 1;
+            start = end + 1;
         }
+        Ok(Worksheet { problems })
+    }
 
+    // Sums the answers of all problems, reading the numbers of each block with `read` and parsing
+    // them as `N`
+    fn grand_total<N: Number>(&self, read: impl Fn(&Problem) -> Vec<String>) -> Result<N, WorksheetError> {
+        self.problems.iter()
+            .enumerate()
+            .try_fold(N::from_usize(0), |total, (i, problem)| {
+                let error = |kind| WorksheetError { problem: i, kind };
+                let answer = problem.op.evaluate(&read(problem)).map_err(error)?;
+                total.add(answer).map_err(|_| error(ErrorKind::TotalOverflow))
+            })
     }
-    calculations.iter().sum()
 }
 
-fn get_sum_calculations(numbers: &[Vec<usize>], operands: &[u8]) -> usize {
-    let mut calculations = numbers[0].to_vec();
+fn main() {
+    if has_flag("--show-problems") {
+        show_problems(&parse("inputs/day06pt1.txt"));
+    }
+    part_1();
+    part_2();
+}
 
-    numbers.iter().skip(1)
-        .for_each(|vec| {
-            vec.iter()
-                .enumerate()
-                .for_each(|(col, &value)| {
-                    if operands[col] == 1 {
-                        calculations[col] *= value;
-                    }
-                    else {
-                        calculations[col] += value;
-                    }
-                });
-        });
+fn part_1() {
+    let worksheet = parse("inputs/day06pt1.txt");
+    print_total("Part 1", &worksheet, Problem::read_rows);
+}
 
-    calculations.iter().sum()
+fn part_2() {
+    let worksheet = parse("inputs/day06pt1.txt");
+    print_total("Part 2", &worksheet, Problem::read_columns_right_to_left);
 }
 
-fn parse(filename: &str) -> (Vec<Vec<usize>>, Vec<u8>) {
-    let re_nums = Regex::new(r"\d+").unwrap();
-    let lines = read_lines(filename);
+// Exact big integer arithmetic with `--big`, checked usize arithmetic otherwise
+fn print_total(label: &str, worksheet: &Worksheet, read: impl Fn(&Problem) -> Vec<String>) {
+    let total = if has_flag("--big") {
+        worksheet.grand_total::<BigInt>(read).map(|sum| format!("{:?}", sum))
+    }
+    else {
+        worksheet.grand_total::<usize>(read).map(|sum| format!("{:?}", sum))
+    };
 
-    let numbers: Vec<Vec<usize>> = lines[..lines.len() - 1]
-        .iter()
-        .map(|line| re_nums.find_iter(line)
-            .map(|m| m.as_str()
-                .parse::<usize>()
-                .unwrap())
-            .collect())
-        .collect();
+    match total {
+        Ok(sum) => println!("{label}: {sum}"),
+        Err(e) => eprintln!("{label}: {e}"),
+    }
+}
 
-    let operands = parse_operands(&lines[lines.len() - 1]);
+// Lists every problem as an equation, read by rows on the left and by columns right to left on the right
+fn show_problems(worksheet: &Worksheet) {
+    let rows = worksheet.problems.iter()
+        .map(|problem| to_equation(problem.op, &problem.read_rows()))
+        .collect::<Vec<String>>();
+    let columns = worksheet.problems.iter()
+        .map(|problem| to_equation(problem.op, &problem.read_columns_right_to_left()));
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
 
-    (numbers, operands)
+    println!("{:>4}  {:<width$} | Columns right to left", "#", "Rows");
+    for (i, (row, column)) in rows.iter().zip(columns).enumerate() {
+        println!("{i:>4}  {row:<width$} | {column}");
+    }
 }
 
-fn parse_numbers_as_string_and_operands(filename: &str) -> (Vec<String>, Vec<u8>) {
-    let lines = read_lines(filename);
-    let operands = parse_operands(&lines[lines.len() - 1]);
-    (lines[..lines.len() - 1].to_vec(), operands)
+fn to_equation(op: Op, numbers: &[String]) -> String {
+    let expression = numbers.join(&format!(" {} ", op.symbol()));
+    let answer = if has_flag("--big") {
+        op.evaluate::<BigInt>(numbers).map(|n| n.to_string())
+    }
+    else {
+        op.evaluate::<usize>(numbers).map(|n| n.to_string())
+    };
+
+    match answer {
+        Ok(answer) => format!("{expression} = {answer}"),
+        Err(e) => format!("{expression} = ({e})"),
+    }
 }
 
-fn parse_operands(line: &str) -> Vec<u8> {
-    let re_operands = Regex::new(r"[*+]").unwrap();
+fn parse(filename: &str) -> Worksheet {
+    Worksheet::from_lines(&read_lines(filename))
+        .unwrap_or_else(|e| panic!("Invalid input: {e}"))
+}
 
-    re_operands.find_iter(line)
-        .map(|m| if m.as_str() == "*" { 1 } else { 0 })
-        .collect()
+// Reads the characters of a row or column as the text of one number, None if it only contains spaces
+fn to_digits<'a>(chars: impl Iterator<Item = &'a char>) -> Option<String> {
+    let digits = chars
+        .filter(|c| **c != ' ')
+        .collect::<String>();
+    if digits.is_empty() {
+        return None;
+    }
+    Some(digits)
+}
+
+// Operands are written as unsigned digits, so stray characters like in `1x3` are an error
+fn to_number<N: Number>(digits: &str) -> Result<N, ErrorKind> {
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ErrorKind::InvalidNumber(digits.to_string()));
+    }
+    N::from_digits(digits)
 }
\ No newline at end of file
@@ -1,121 +1,488 @@
-use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-use utils::read_lines;
+use utils::rng::XorShift;
+use utils::viz::{Cell, Color, Frame, Visualizer};
+use utils::{flag_value, has_flag, read_lines};
 
-fn main() {
-    let (splitters, start, width) = parse("inputs/day07pt1.txt");
-    let (visited, endpoints) =  get_visited_splitters_and_endpoints(&splitters, start, width);
-    part_1(&visited);
-    part_2(visited, endpoints, width);
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tile {
+    // `S`, starts a new timeline
+    Source,
+    // `^`, sends the beam to both sides
+    Splitter,
+    // `/` and `\`, send the beam to the left or right only
+    MirrorLeft,
+    MirrorRight,
+    // `#`, swallows the beam
+    Absorber,
 }
 
-fn part_1(visited: &HashSet<(usize, usize)>) {
-    println!("Part 1: {:?}", visited.len());
+impl Tile {
+    fn from_char(c: char) -> Option<Tile> {
+        match c {
+            'S' => Some(Tile::Source),
+            '^' => Some(Tile::Splitter),
+            '/' => Some(Tile::MirrorLeft),
+            '\\' => Some(Tile::MirrorRight),
+            '#' => Some(Tile::Absorber),
+            _ => None,
+        }
+    }
+
+    // Sideways moves of a beam hitting this tile, empty if the beam is swallowed
+    fn offsets(self, spread: usize) -> Vec<isize> {
+        let spread = spread as isize;
+        match self {
+            Tile::Source | Tile::Absorber => vec![],
+            Tile::Splitter => vec![-spread, spread],
+            Tile::MirrorLeft => vec![-spread],
+            Tile::MirrorRight => vec![spread],
+        }
+    }
+
+    fn to_cell(self) -> Cell {
+        match self {
+            Tile::Source => Cell::new('S', Color::Yellow),
+            Tile::Splitter => Cell::new('^', Color::Gray),
+            Tile::MirrorLeft => Cell::new('/', Color::Blue),
+            Tile::MirrorRight => Cell::new('\\', Color::Blue),
+            Tile::Absorber => Cell::new('#', Color::Magenta),
+        }
+    }
 }
 
-fn part_2(visited: HashSet<(usize, usize)>, endpoints: Vec<(usize, usize)>, width: usize) {
-    let mut timeline_map: HashMap<(usize, usize), usize> = HashMap::new();
-    let start = visited.iter().min_by_key(|x| x.0).unwrap().to_owned();
-    let height = visited.iter().max_by_key(|x| x.0).unwrap().0;
+#[derive(Debug, Clone, Copy)]
+enum Edge {
+    Lost,
+    Reflect,
+}
 
-    let timelines = calculate_number_timelines(&visited, &endpoints, start, &mut timeline_map, width, height);
-    println!("Part 2: {:?}", timelines);
+#[derive(Debug)]
+struct Physics {
+    // How many columns a deflected beam moves sideways
+    spread: usize,
+    edge: Edge,
 }
 
-fn calculate_number_timelines(
-    visited: &HashSet<(usize, usize)>,
-    endpoints: &[(usize, usize)],
-    current: (usize, usize),
-    timeline_map: &mut HashMap<(usize, usize), usize>,
+impl Physics {
+    fn from_args() -> Physics {
+        let spread = flag_value("--spread")
+            .map(|s| s.parse::<usize>().expect("Could not parse spread"))
+            .unwrap_or(1);
+        // A spread of 0 would send both halves of a split back into the splitter's own column
+        assert!(spread >= 1, "Spread must be at least 1");
+        let edge = if has_flag("--reflect") { Edge::Reflect } else { Edge::Lost };
+        Physics { spread, edge }
+    }
+
+    // Column a beam in `col` ends up in after moving `offset` columns, None if it leaves the manifold
+    fn shift(&self, col: usize, offset: isize, width: usize) -> Option<usize> {
+        let target = col as isize + offset;
+        let width = width as isize;
+        if (0..width).contains(&target) {
+            return Some(target as usize);
+        }
+        match self.edge {
+            Edge::Lost => None,
+            Edge::Reflect => {
+                // Bounce between both walls until the beam is back inside
+                let folded = target.rem_euclid(2 * width);
+                Some(if folded < width { folded } else { 2 * width - 1 - folded } as usize)
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Manifold {
     width: usize,
-    height: usize,
-) -> usize {
-    let mut timelines = 0;
+    // Tiles of every row, sorted by column
+    rows: Vec<Vec<(usize, Tile)>>,
+}
 
-    if let Some(paths) = timeline_map.get(&current) {
-        return *paths;
+impl Manifold {
+    fn tile(&self, row: usize, col: usize) -> Option<Tile> {
+        let tiles = &self.rows[row];
+        tiles.binary_search_by_key(&col, |&(c, _)| c)
+            .ok()
+            .map(|i| tiles[i].1)
     }
 
-    let cols = get_valid_neighbors(&current.1, width);
+    fn sources(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.rows.iter()
+            .enumerate()
+            .flat_map(|(row_no, row)| {
+                row.iter()
+                    .filter(|(_, tile)| *tile == Tile::Source)
+                    .map(move |&(col, _)| (row_no, col))
+            })
+    }
+}
 
-    for col in cols {
-        let mut row = current.0;
-        let mut count = false;
+#[derive(Debug)]
+struct BeamReport {
+    splits: usize,
+    timelines: u128,
+    // Timelines that left the manifold sideways or hit an absorber
+    lost: u128,
+    // Number of timelines leaving the bottom of the manifold in every column
+    exits: Vec<u128>,
+}
 
-        while row < height {
-            row += 1;
+#[derive(Debug, Clone, Copy)]
+enum Choice {
+    Left,
+    Right,
+}
 
-            if visited.contains(&(row, col)) {
-                count = true;
-                timelines += calculate_number_timelines(visited, endpoints, (row, col), timeline_map, width, height);
-                break;
+#[derive(Debug)]
+struct Timeline {
+    source: (usize, usize),
+    // The side taken at every splitter along the way
+    choices: Vec<Choice>,
+    exit: usize,
+}
+
+impl fmt::Display for Timeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let choices = self.choices.iter()
+            .map(|choice| match choice {
+                Choice::Left => 'L',
+                Choice::Right => 'R',
+            })
+            .collect::<String>();
+        write!(f, "S{:?} {choices} -> column {}", self.source, self.exit)
+    }
+}
+
+// Position of a single beam: it is in `col` of `row` and was last deflected there by the tile in
+// column `after`. Tiles in a row act from left to right, so it only hits tiles further right
+#[derive(Debug, Clone, Copy)]
+struct BeamState {
+    row: usize,
+    col: usize,
+    after: Option<usize>,
+}
+
+// Where a tile sends a beam, None if it gets lost, and the side taken if the tile is a splitter
+type Branch = (Option<BeamState>, Option<Choice>);
+
+// Counts the surviving timelines from every position bottom-up, which allows following single
+// timelines without walking into branches that all get lost
+struct TimelineCounts<'a> {
+    manifold: &'a Manifold,
+    physics: &'a Physics,
+    // entering[r][c]: timelines of a beam in column c before the tiles of row r act on it
+    entering: Vec<Vec<u128>>,
+}
+
+impl<'a> TimelineCounts<'a> {
+    fn new(manifold: &'a Manifold, physics: &'a Physics) -> TimelineCounts<'a> {
+        let height = manifold.rows.len();
+        let mut counts = TimelineCounts {
+            manifold,
+            physics,
+            entering: vec![vec![0; manifold.width]; height + 1],
+        };
+        counts.entering[height] = vec![1; manifold.width];
+
+        for row in (0..height).rev() {
+            // Right to left, so deflections to the right within a row are already known
+            for col in (0..manifold.width).rev() {
+                let state = BeamState { row, col, after: None };
+                counts.entering[row][col] = counts.branches(state)
+                    .map(|branches| {
+                        branches.into_iter()
+                            .filter_map(|(next, _)| next)
+                            .map(|next| counts.count(next))
+                            .sum()
+                    })
+                    .unwrap_or(counts.entering[row + 1][col]);
             }
+        }
+        counts
+    }
+
+    // Where the tile at the beam's position sends it, None if there is no tile acting on it
+    fn branches(&self, state: BeamState) -> Option<Vec<Branch>> {
+        if state.row >= self.manifold.rows.len() {
+            return None;
+        }
+        let tile = self.manifold.tile(state.row, state.col)?;
+        if tile == Tile::Source || state.after.is_some_and(|after| state.col <= after) {
+            return None;
+        }
+
+        let branches = tile.offsets(self.physics.spread)
+            .into_iter()
+            .map(|offset| {
+                let next = self.physics.shift(state.col, offset, self.manifold.width)
+                    .map(|col| BeamState { row: state.row, col, after: Some(state.col) });
+                let choice = match (tile, offset < 0) {
+                    (Tile::Splitter, true) => Some(Choice::Left),
+                    (Tile::Splitter, false) => Some(Choice::Right),
+                    _ => None,
+                };
+                (next, choice)
+            })
+            .collect();
+        Some(branches)
+    }
+
+    fn count(&self, state: BeamState) -> u128 {
+        if state.row >= self.manifold.rows.len() {
+            1
+        }
+        else if self.branches(state).is_some() {
+            self.entering[state.row][state.col]
+        }
+        else {
+            self.entering[state.row + 1][state.col]
+        }
+    }
+
+    fn total(&self) -> u128 {
+        self.manifold.sources()
+            .map(|(row, col)| self.entering[row + 1][col])
+            .sum()
+    }
+
+    // The first `limit` timelines, taking the left side of a splitter first
+    fn enumerate(&self, limit: usize) -> Vec<Timeline> {
+        let mut timelines = vec![];
+        for source in self.manifold.sources() {
+            let start = BeamState { row: source.0 + 1, col: source.1, after: None };
+            self.enumerate_from(start, source, &mut vec![], &mut timelines, limit);
+        }
+        timelines
+    }
 
-            if endpoints.contains(&(row, col)) {
-                timelines += 1;
-                count = true;
+    fn enumerate_from(
+        &self,
+        mut state: BeamState,
+        source: (usize, usize),
+        choices: &mut Vec<Choice>,
+        timelines: &mut Vec<Timeline>,
+        limit: usize,
+    ) {
+        loop {
+            if timelines.len() >= limit || state.row >= self.manifold.rows.len() {
                 break;
             }
+            if self.count(state) == 0 {
+                return;
+            }
+            match self.branches(state) {
+                Some(branches) => {
+                    for (next, choice) in branches {
+                        let Some(next) = next else { continue };
+                        choices.extend(choice);
+                        self.enumerate_from(next, source, choices, timelines, limit);
+                        if choice.is_some() {
+                            choices.pop();
+                        }
+                    }
+                    return;
+                },
+                None => state = BeamState { row: state.row + 1, col: state.col, after: None },
+            }
         }
-        if !count && row >= height {
-            timelines += 1;
+        if timelines.len() < limit {
+            timelines.push(Timeline { source, choices: choices.clone(), exit: state.col });
         }
     }
 
-    timeline_map.insert(current, timelines);
+    // Picks every surviving timeline with the same probability by weighting each branch with its count
+    fn sample(&self, rng: &mut XorShift) -> Option<Timeline> {
+        let sources = self.manifold.sources()
+            .map(|(row, col)| ((row, col), BeamState { row: row + 1, col, after: None }))
+            .collect::<Vec<_>>();
+        let (source, mut state) = pick_weighted(rng, sources, |(_, state)| self.count(*state))?;
+        let mut choices = vec![];
 
-    timelines
+        while state.row < self.manifold.rows.len() {
+            state = match self.branches(state) {
+                Some(branches) => {
+                    let alive = branches.into_iter()
+                        .filter_map(|(next, choice)| next.map(|next| (next, choice)))
+                        .collect::<Vec<_>>();
+                    let (next, choice) = pick_weighted(rng, alive, |(next, _)| self.count(*next))?;
+                    choices.extend(choice);
+                    next
+                },
+                None => BeamState { row: state.row + 1, col: state.col, after: None },
+            };
+        }
+        Some(Timeline { source, choices, exit: state.col })
+    }
+}
 
+fn pick_weighted<T>(rng: &mut XorShift, options: Vec<T>, weight: impl Fn(&T) -> u128) -> Option<T> {
+    let total: u128 = options.iter().map(&weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = ((rng.next_u64() as u128) << 64 | rng.next_u64() as u128) % total;
+    for option in options {
+        let w = weight(&option);
+        if target < w {
+            return Some(option);
+        }
+        target -= w;
+    }
+    None
 }
 
-fn get_visited_splitters_and_endpoints(
-    splitters: &[Vec<usize>],
-    start: usize, width: usize,
-) -> (HashSet<(usize, usize)>, Vec<(usize, usize)>) {
-    let mut visited = HashSet::new();
+fn main() {
+    let manifold = parse("inputs/day07pt1.txt");
+    let physics = Physics::from_args();
+    let mut visualizer = Visualizer::from_args();
+    let mut frame = Frame::new(manifold.width, manifold.rows.len());
+    if let Some(visualizer) = visualizer.as_mut() {
+        draw_manifold(&mut frame, &manifold);
+        visualizer.show(&frame).expect("Failed to show frame");
+    }
 
-    let mut current = vec![(0, start)];
-    for (row_no, row) in splitters.iter().enumerate() {
-        for splitter in row {
-            let current_cols: Vec<_> = current.iter().map(|(_, col)| *col).collect();
-            if current_cols.contains(splitter) {
-                get_valid_neighbors(splitter, width)
-                    .iter().for_each(|&x| {
-                    current.push((row_no, x));
-                });
-                current.retain(|&x| x.1 != *splitter);
-                visited.insert((row_no, *splitter));
-            }
+    let report = simulate_beams(&manifold, &physics, |row_no, timelines, hit| {
+        if let Some(visualizer) = visualizer.as_mut() {
+            draw_beams(&mut frame, row_no, timelines, hit);
+            visualizer.show(&frame).expect("Failed to show frame");
         }
+    });
+    part_1(&report);
+    part_2(&report);
+    if report.lost > 0 {
+        println!("Lost timelines: {:?}", report.lost);
     }
-    (visited, current)
+    explore_timelines(&manifold, &physics, &report);
 }
 
-fn get_valid_neighbors(pos: &usize, width: usize) -> Vec<usize> {
-    if *pos == 0 {
-        return vec![1];
+fn part_1(report: &BeamReport) {
+    println!("Part 1: {:?}", report.splits);
+}
+
+fn part_2(report: &BeamReport) {
+    println!("Part 2: {:?}", report.timelines);
+}
+
+// Lists timelines with `--timelines N`, samples them with `--sample N [--seed S]` and shows
+// where they leave the manifold with `--exits`
+fn explore_timelines(manifold: &Manifold, physics: &Physics, report: &BeamReport) {
+    let limit = flag_value("--timelines").map(|s| s.parse::<usize>().expect("Could not parse number of timelines"));
+    let samples = flag_value("--sample").map(|s| s.parse::<usize>().expect("Could not parse number of samples"));
+    let exits = has_flag("--exits");
+    if limit.is_none() && samples.is_none() && !exits {
+        return;
     }
-    if *pos == width - 1 {
-        return vec![width - 2];
+
+    let counts = TimelineCounts::new(manifold, physics);
+    println!("Timelines counted bottom-up: {:?}", counts.total());
+
+    if let Some(limit) = limit {
+        counts.enumerate(limit)
+            .iter()
+            .for_each(|timeline| println!("{timeline}"));
     }
-    vec![pos - 1, pos + 1]
+    if let Some(samples) = samples {
+        let seed = flag_value("--seed")
+            .map(|s| s.parse::<u64>().expect("Could not parse seed"))
+            .unwrap_or_else(|| XorShift::from_time().next_u64());
+        let mut rng = XorShift::new(seed);
+        println!("Sampling with seed {seed}");
+        (0..samples)
+            .filter_map(|_| counts.sample(&mut rng))
+            .for_each(|timeline| println!("{timeline}"));
+    }
+    if exits {
+        report.exits.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .for_each(|(col, count)| {
+                let share = *count as f64 / report.timelines as f64 * 100.0;
+                println!("Column {col}: {count} ({share:.2}%)");
+            });
+    }
+}
+
+// Walks down the manifold once, keeping the number of timelines that have a beam in every column.
+// A hit tile hands its timelines to the columns it deflects to, so both the splits and the
+// timelines are counted in O(width * height). `on_row` gets the counts and hit tiles of every row
+fn simulate_beams(
+    manifold: &Manifold,
+    physics: &Physics,
+    mut on_row: impl FnMut(usize, &[u128], &[usize]),
+) -> BeamReport {
+    let mut timelines = vec![0_u128; manifold.width];
+    let mut splits = 0;
+    let mut lost = 0;
+
+    for (row_no, row) in manifold.rows.iter().enumerate() {
+        let mut hit = vec![];
+        for &(col, tile) in row {
+            if tile == Tile::Source {
+                timelines[col] += 1;
+                continue;
+            }
+            let offsets = tile.offsets(physics.spread);
+
+            let count = timelines[col];
+            if count == 0 {
+                continue;
+            }
+            timelines[col] = 0;
+            if offsets.is_empty() {
+                lost += count;
+            }
+            for offset in offsets {
+                match physics.shift(col, offset, manifold.width) {
+                    Some(target) => timelines[target] += count,
+                    None => lost += count,
+                }
+            }
+            if tile == Tile::Splitter {
+                splits += 1;
+            }
+            hit.push(col);
+        }
+        on_row(row_no, &timelines, &hit);
+    }
+
+    BeamReport { splits, timelines: timelines.iter().sum(), lost, exits: timelines }
 }
 
-fn parse(filename: &str) -> (Vec<Vec<usize>>, usize, usize) {
-    let mut splitters: Vec<Vec<usize>> = vec![];
+fn draw_manifold(frame: &mut Frame, manifold: &Manifold) {
+    manifold.rows.iter()
+        .enumerate()
+        .for_each(|(row_no, row)| {
+            row.iter().for_each(|&(col, tile)| frame.set(row_no, col, tile.to_cell()));
+        });
+}
 
+// Beams travelling down in cyan through empty cells, the tiles they hit in red
+fn draw_beams(frame: &mut Frame, row: usize, timelines: &[u128], hit: &[usize]) {
+    hit.iter()
+        .for_each(|&col| {
+            let ch = frame.get(row, col).ch;
+            frame.set(row, col, Cell::new(ch, Color::Red));
+        });
+    for (col, &count) in timelines.iter().enumerate() {
+        if count > 0 && frame.get(row, col).ch == '.' {
+            frame.set(row, col, Cell::new('|', Color::Cyan));
+        }
+    }
+}
+
+fn parse(filename: &str) -> Manifold {
     let lines = read_lines(filename);
-    let width = lines[0].len();
-    let start = lines[0].chars().position(|c| c == 'S').unwrap();
-    lines.iter()
-        .skip(1)
-        .for_each(|line| {
-            splitters.push(line.chars()
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let rows = lines.iter()
+        .map(|line| {
+            line.chars()
                 .enumerate()
-                .filter(|(_, c)| *c == '^')
-                .map(|(col, _)| col )
-                .collect::<Vec<_>>());
-        });
+                .filter_map(|(col, c)| Tile::from_char(c).map(|tile| (col, tile)))
+                .collect::<Vec<_>>()
+        })
+        .collect();
 
-    (splitters, start, width)
+    Manifold { width, rows }
 }
\ No newline at end of file
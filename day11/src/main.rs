@@ -1,75 +1,323 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 
-use utils::read_lines;
+use utils::graph::{Cycle, Graph, NodeId};
+use utils::{flag_value, read_input};
+
+// Devices, by name, that feed into each other in a loop on the way from start to end
+#[derive(Debug)]
+struct CycleError {
+    devices: Vec<String>,
+}
+
+impl CycleError {
+    fn new(devices: &Graph, cycle: &Cycle) -> CycleError {
+        CycleError { devices: cycle.nodes.iter().map(|&id| devices.name(id).to_string()).collect() }
+    }
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "devices form a cycle, the number of paths is unbounded: {} -> {}", self.devices.join(" -> "), self.devices[0])
+    }
+}
+
+// Every device on a path from start to end keeps one count per subset of waypoints, with 16
+// waypoints that is 512 KiB per device already, so more would quickly run out of memory
+const MAX_WAYPOINTS: usize = 16;
+
+#[derive(Debug)]
+enum PathError {
+    Cycle(CycleError),
+    TooManyWaypoints(usize),
+}
+
+impl From<CycleError> for PathError {
+    fn from(error: CycleError) -> PathError {
+        PathError::Cycle(error)
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::Cycle(error) => write!(f, "{error}"),
+            PathError::TooManyWaypoints(count) => write!(f, "{count} waypoints, at most {MAX_WAYPOINTS} are supported"),
+        }
+    }
+}
 
 fn main() {
+    if let Some(filename) = flag_value("--dot") {
+        export_dot(&filename);
+        return;
+    }
+    if let (Some(from), Some(to)) = (flag_value("--from"), flag_value("--to")) {
+        custom(&from, &to);
+        return;
+    }
     part_1();
     part_2();
 }
 
 fn part_1() {
     let devices = parse("inputs/day11pt1.txt");
-    let mut cache: HashMap<String, usize> = HashMap::new();
-    let total_paths = count_paths(&devices, String::from("you"), "out", &mut cache);
-    println!("Part 1: {:?}", total_paths);
+    match count_paths(&devices, "you", "out") {
+        Ok(total_paths) => println!("Part 1: {:?}", total_paths),
+        Err(e) => eprintln!("Part 1: {e}"),
+    }
 }
 
 fn part_2() {
     let devices = parse("inputs/day11pt1.txt");
-    let mut cache: HashMap<String, usize> = HashMap::new();
-    // Both orders could be possible, and we don't know which one is correct
-    let first = count_paths(&devices, String::from("svr"), "fft", &mut cache);
-    cache.clear();
-    let second = count_paths(&devices, String::from("fft"), "dac", &mut cache);
-    cache.clear();
-    let third = count_paths(&devices, String::from("dac"), "out", &mut cache);
-    let mut total_paths = first * second * third;
-
-    cache.clear();
-    let first = count_paths(&devices, String::from("svr"), "dac", &mut cache);
-    cache.clear();
-    let second = count_paths(&devices, String::from("dac"), "fft", &mut cache);
-    cache.clear();
-    let third = count_paths(&devices, String::from("fft"), "out", &mut cache);
-    total_paths += first * second * third;
-    println!("Part 2: {:?}", total_paths);
+    match count_paths_via(&devices, "svr", "out", &["fft", "dac"], &[]) {
+        Ok(total_paths) => println!("Part 2: {:?}", total_paths),
+        Err(e) => eprintln!("Part 2: {e}"),
+    }
 }
 
-fn count_paths(devices: &HashMap<String, Vec<String>>, current: String, end: &str, cache: &mut HashMap<String, usize>) -> usize {
-    let mut paths = 0;
+// Paths between any two devices with `--from X --to Y [--via a,b,..] [--avoid c,d,..]`
+fn custom(from: &str, to: &str) {
+    let devices = parse("inputs/day11pt1.txt");
+    let via = flag_value("--via").unwrap_or_default();
+    let avoid = flag_value("--avoid").unwrap_or_default();
 
-    if current == end {
-        paths += 1;
+    match count_paths_via(&devices, from, to, &split_names(&via), &split_names(&avoid)) {
+        Ok(total_paths) => println!("Paths from {from} to {to}: {:?}", total_paths),
+        Err(e) => eprintln!("Paths from {from} to {to}: {e}"),
     }
+}
+
+// Writes the devices as a Graphviz digraph, render it with `dot -Tsvg FILE -o devices.svg`.
+// Start, end and waypoint devices come from `--from`, `--to` and `--via` and default to the puzzle's
+fn export_dot(filename: &str) {
+    let devices = parse("inputs/day11pt1.txt");
+    let end = flag_value("--to").unwrap_or(String::from("out"));
+    let starts = flag_value("--from").map_or(vec![String::from("you"), String::from("svr")], |from| vec![from]);
+    let waypoints = flag_value("--via").unwrap_or(String::from("fft,dac"));
+    let waypoints = split_names(&waypoints);
+
+    let paths = match paths_to_end(&devices, &end) {
+        Ok(paths) => Some(paths),
+        Err(e) => {
+            eprintln!("Exporting without path counts: {e}");
+            None
+        },
+    };
 
-    if let Some(cached) = cache.get(&current) {
-        return *cached;
+    let mut dot = String::from("digraph devices {\n    rankdir=LR;\n    node [shape=box];\n");
+    for node in 0..devices.len() {
+        let name = devices.name(node);
+        // `\n` is a line break within a DOT label, so it goes in after escaping the names
+        let mut label = escape(name);
+        if let Some(paths) = &paths {
+            label.push_str(&format!("\\n{} paths to {}", paths[node], escape(&end)));
+        }
+        let fill = if starts.iter().any(|start| start == name) {
+            ", style=filled, fillcolor=palegreen"
+        }
+        else if name == end {
+            ", style=filled, fillcolor=lightcoral"
+        }
+        else if waypoints.contains(&name) {
+            ", style=filled, fillcolor=lightskyblue"
+        }
+        else {
+            ""
+        };
+        dot.push_str(&format!("    {} [label=\"{label}\"{fill}];\n", quote(name)));
     }
+    for node in 0..devices.len() {
+        for &next in devices.neighbors(node) {
+            dot.push_str(&format!("    {} -> {};\n", quote(devices.name(node)), quote(devices.name(next))));
+        }
+    }
+    dot.push_str("}\n");
+
+    fs::write(filename, dot).expect("Failed to write dot file");
+    println!("Wrote {} devices to {filename}", devices.len());
+}
 
-    if let Some(options) = devices.get(&current) {
-        for option in options {
-            paths += count_paths(devices, option.to_string(), end, cache);
+fn quote(name: &str) -> String {
+    format!("\"{}\"", escape(name))
+}
+
+// Backslashes first, otherwise the ones escaping quotes would be doubled again
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Number of paths from every device to `end`, zero for devices that can not reach it
+fn paths_to_end(devices: &Graph, end: &str) -> Result<Vec<usize>, CycleError> {
+    let Some(end) = devices.id(end) else {
+        return Ok(vec![0; devices.len()]);
+    };
+    let relevant = devices.induced(&devices.reversed().reachable_from(end));
+
+    relevant.dp_over_successors(|node, paths: &[usize]| {
+        if node == end {
+            return 1;
         }
+        relevant.neighbors(node).iter().map(|&next| paths[next]).sum()
+    }).map_err(|cycle| CycleError::new(devices, &cycle))
+}
+
+fn split_names(list: &str) -> Vec<&str> {
+    list.split(",")
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn count_paths(devices: &Graph, start: &str, end: &str) -> Result<usize, PathError> {
+    count_paths_via(devices, start, end, &[], &[])
+}
+
+// Paths from start to end that pass every waypoint, in any order, and none of the forbidden devices.
+// Going backwards through the devices in topological order, every device keeps the number of paths
+// to `end` for each subset of waypoints they visit, indexed by bitmask. That is O(edges * 2^waypoints).
+// Only devices on some path from start to end are considered, a loop among them is an error
+fn count_paths_via(
+    devices: &Graph,
+    start: &str,
+    end: &str,
+    waypoints: &[&str],
+    forbidden: &[&str],
+) -> Result<usize, PathError> {
+    let (Some(start), Some(end)) = (devices.id(start), devices.id(end)) else {
+        return Ok(0);
+    };
+    let Some(mut waypoints) = waypoints.iter().map(|name| devices.id(name)).collect::<Option<Vec<NodeId>>>() else {
+        return Ok(0);
+    };
+    waypoints.sort();
+    waypoints.dedup();
+    if waypoints.len() > MAX_WAYPOINTS {
+        return Err(PathError::TooManyWaypoints(waypoints.len()));
+    }
+    let forbidden = forbidden.iter()
+        .filter_map(|name| devices.id(name))
+        .collect::<Vec<NodeId>>();
+    if forbidden.contains(&start) || forbidden.contains(&end) {
+        return Ok(0);
     }
 
-    cache.insert(current, paths);
+    let relevant = paths_subgraph(devices, start, end, &forbidden);
+    let bit = |node| waypoints.iter().position(|&w| w == node).map_or(0, |i| 1 << i);
+    let subsets = 1 << waypoints.len();
 
-    paths
+    let paths = relevant.dp_over_successors(|node, paths: &[Vec<usize>]| {
+        // Devices off every path keep an empty list, which stands for no paths at all
+        if node != end && relevant.neighbors(node).is_empty() {
+            return Vec::new();
+        }
+        let mut through = vec![0; subsets];
+        if node == end {
+            through[0] = 1;
+        }
+        else {
+            for &next in relevant.neighbors(node) {
+                through.iter_mut()
+                    .zip(&paths[next])
+                    .for_each(|(total, count)| *total += count);
+            }
+        }
+
+        // Passing this device adds it to the visited waypoints, moving every count without its bit
+        let node_bit = bit(node);
+        if node_bit != 0 {
+            for mask in (0..subsets).filter(|mask| mask & node_bit == 0) {
+                through[mask | node_bit] += through[mask];
+                through[mask] = 0;
+            }
+        }
+        through
+    }).map_err(|cycle| CycleError::new(devices, &cycle))?;
+    Ok(paths[start].get(subsets - 1).copied().unwrap_or(0))
 }
 
-fn parse(filename: &str) -> HashMap<String, Vec<String>> {
-    let mut devices: HashMap<String, Vec<String>> = HashMap::new();
+// Keeps only the edges between devices that lie on some path from start to end avoiding `forbidden`
+fn paths_subgraph(devices: &Graph, start: NodeId, end: NodeId, forbidden: &[NodeId]) -> Graph {
+    let mut allowed = vec![true; devices.len()];
+    forbidden.iter().for_each(|&node| allowed[node] = false);
+    let allowed_devices = devices.induced(&allowed);
 
-    read_lines(filename)
-        .into_iter()
-        .for_each(|line| {
-            let split : Vec<&str> = line.split(": ").collect();
-            let key = split[0].to_string();
-            let value = split[1].split(" ")
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>();
+    let from_start = allowed_devices.reachable_from(start);
+    let to_end = allowed_devices.reversed().reachable_from(end);
+    let on_path = from_start.iter()
+        .zip(&to_end)
+        .map(|(&a, &b)| a && b)
+        .collect::<Vec<bool>>();
+    allowed_devices.induced(&on_path)
+}
 
-            devices.insert(key, value);
+fn parse(filename: &str) -> Graph {
+    parse_devices(&read_input(filename))
+}
+
+fn parse_devices(input: &str) -> Graph {
+    let mut devices = Graph::new();
+
+    input.lines()
+        .filter(|line| !line.is_empty())
+        .for_each(|line| {
+            let (device, outputs) = line.split_once(": ").expect("Device line should contain ': '");
+            devices.intern(device);
+            outputs.split(" ")
+                .for_each(|output| devices.add_edge(device, output));
         });
     devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_paths_without_loops() {
+        let devices = parse_devices("you: a b\na: c out\nb: c\nc: out\n");
+        assert_eq!(count_paths(&devices, "you", "out").unwrap(), 3);
+    }
+
+    #[test]
+    fn loop_between_start_and_end_is_an_error() {
+        let devices = parse_devices("you: a\na: b\nb: c out\nc: a\n");
+        let Err(PathError::Cycle(error)) = count_paths(&devices, "you", "out") else {
+            panic!("Expected a cycle error");
+        };
+        let mut looping = error.devices;
+        looping.sort();
+        assert_eq!(looping, ["a", "b", "c"]);
+    }
+
+    // A loop that can not reach the end does not change the number of paths
+    #[test]
+    fn loop_off_the_paths_is_ignored() {
+        let devices = parse_devices("you: a out\na: b\nb: a\n");
+        assert_eq!(count_paths(&devices, "you", "out").unwrap(), 1);
+    }
+
+    #[test]
+    fn too_many_waypoints_is_an_error() {
+        let names = (0..=MAX_WAYPOINTS).map(|i| format!("d{i}")).collect::<Vec<String>>();
+        let mut input = format!("you: {}\n", names[0]);
+        for pair in names.windows(2) {
+            input.push_str(&format!("{}: {}\n", pair[0], pair[1]));
+        }
+        input.push_str(&format!("{}: out\n", names[MAX_WAYPOINTS]));
+        let devices = parse_devices(&input);
+
+        let waypoints = names.iter().map(String::as_str).collect::<Vec<&str>>();
+        assert!(matches!(
+            count_paths_via(&devices, "you", "out", &waypoints, &[]),
+            Err(PathError::TooManyWaypoints(count)) if count == MAX_WAYPOINTS + 1
+        ));
+        assert_eq!(count_paths_via(&devices, "you", "out", &waypoints[..MAX_WAYPOINTS], &[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn quote_escapes_backslashes_and_quotes() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote(r#"a\b"c"#), r#""a\\b\"c""#);
+        assert_eq!(quote(r"end\"), r#""end\\""#);
+    }
 }
\ No newline at end of file
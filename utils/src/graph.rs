@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+
+pub type NodeId = usize;
+
+// Nodes forming a directed cycle, every node has an edge to the next and the last one back to the first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub nodes: Vec<NodeId>,
+}
+
+// Directed graph over named nodes. Names are interned once, after that everything works on
+// integer ids indexing into plain adjacency lists
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, NodeId>,
+    edges: Vec<Vec<NodeId>>,
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        Graph::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> NodeId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.edges.push(Vec::new());
+        id
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str) {
+        let from = self.intern(from);
+        let to = self.intern(to);
+        self.edges[from].push(to);
+    }
+
+    pub fn id(&self, name: &str) -> Option<NodeId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn neighbors(&self, id: NodeId) -> &[NodeId] {
+        &self.edges[id]
+    }
+
+    pub fn reversed(&self) -> Graph {
+        let mut edges = vec![Vec::new(); self.len()];
+        for (from, targets) in self.edges.iter().enumerate() {
+            for &to in targets {
+                edges[to].push(from);
+            }
+        }
+        Graph { names: self.names.clone(), ids: self.ids.clone(), edges }
+    }
+
+    // Same nodes and ids, but only the edges between nodes marked in `keep`
+    pub fn induced(&self, keep: &[bool]) -> Graph {
+        let edges = self.edges.iter()
+            .enumerate()
+            .map(|(from, targets)| {
+                if !keep[from] {
+                    return Vec::new();
+                }
+                targets.iter().copied().filter(|&to| keep[to]).collect()
+            })
+            .collect();
+        Graph { names: self.names.clone(), ids: self.ids.clone(), edges }
+    }
+
+    // Breadth-first search, marks every node reachable from `start` including itself
+    pub fn reachable_from(&self, start: NodeId) -> Vec<bool> {
+        let mut seen = vec![false; self.len()];
+        let mut queue = VecDeque::from([start]);
+        seen[start] = true;
+
+        while let Some(node) = queue.pop_front() {
+            for &next in &self.edges[node] {
+                if !seen[next] {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    // Kahn's algorithm, every edge points from an earlier to a later node in the order
+    pub fn topological_order(&self) -> Result<Vec<NodeId>, Cycle> {
+        let mut in_degree = vec![0; self.len()];
+        self.edges.iter()
+            .flatten()
+            .for_each(|&to| in_degree[to] += 1);
+
+        let mut queue = (0..self.len())
+            .filter(|&node| in_degree[node] == 0)
+            .collect::<VecDeque<NodeId>>();
+        let mut order = Vec::with_capacity(self.len());
+
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for &next in &self.edges[node] {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        if order.len() < self.len() {
+            return Err(self.find_cycle().expect("Nodes left over by a topological sort lie on a cycle"));
+        }
+        Ok(order)
+    }
+
+    // Iterative depth-first search with white/gray/black coloring, an edge back to a gray node
+    // closes a cycle made of the gray nodes on the stack
+    pub fn find_cycle(&self) -> Option<Cycle> {
+        const WHITE: u8 = 0;
+        const GRAY: u8 = 1;
+        const BLACK: u8 = 2;
+        let mut color = vec![WHITE; self.len()];
+
+        for start in 0..self.len() {
+            if color[start] != WHITE {
+                continue;
+            }
+            color[start] = GRAY;
+            let mut stack: Vec<(NodeId, usize)> = vec![(start, 0)];
+
+            while let Some((node, next_edge)) = stack.last_mut() {
+                let node = *node;
+                let Some(&next) = self.edges[node].get(*next_edge) else {
+                    color[node] = BLACK;
+                    stack.pop();
+                    continue;
+                };
+                *next_edge += 1;
+
+                match color[next] {
+                    WHITE => {
+                        color[next] = GRAY;
+                        stack.push((next, 0));
+                    },
+                    GRAY => {
+                        let pos = stack.iter().position(|&(n, _)| n == next).unwrap();
+                        let nodes = stack[pos..].iter().map(|&(n, _)| n).collect();
+                        return Some(Cycle { nodes });
+                    },
+                    _ => {},
+                }
+            }
+        }
+        None
+    }
+
+    // Computes a value for every node after the values of all its successors, `f` gets the node
+    // and the values computed so far, indexed by node id
+    pub fn dp_over_successors<T: Clone + Default>(
+        &self,
+        mut f: impl FnMut(NodeId, &[T]) -> T,
+    ) -> Result<Vec<T>, Cycle> {
+        let order = self.topological_order()?;
+        let mut values = vec![T::default(); self.len()];
+
+        for &node in order.iter().rev() {
+            values[node] = f(node, &values);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new();
+        edges.iter().for_each(|&(from, to)| graph.add_edge(from, to));
+        graph
+    }
+
+    // The cycle's nodes, by name, rotated to start at the smallest one
+    fn cycle_names(graph: &Graph, cycle: &Cycle) -> Vec<String> {
+        let mut names = cycle.nodes.iter().map(|&id| graph.name(id).to_string()).collect::<Vec<String>>();
+        let first = names.iter().enumerate().min_by_key(|(_, name)| *name).map_or(0, |(i, _)| i);
+        names.rotate_left(first);
+        names
+    }
+
+    #[test]
+    fn topological_order_puts_edges_forward() {
+        let g = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("e", "a")]);
+        let order = g.topological_order().unwrap();
+        assert_eq!(order.len(), g.len());
+
+        let mut position = vec![0; g.len()];
+        order.iter().enumerate().for_each(|(i, &node)| position[node] = i);
+        for from in 0..g.len() {
+            for &to in g.neighbors(from) {
+                assert!(position[from] < position[to], "{} before {}", g.name(from), g.name(to));
+            }
+        }
+    }
+
+    #[test]
+    fn topological_order_reports_cycle() {
+        let g = graph(&[("start", "a"), ("a", "b"), ("b", "a"), ("b", "end")]);
+        let cycle = g.topological_order().unwrap_err();
+        assert_eq!(cycle_names(&g, &cycle), ["a", "b"]);
+    }
+
+    #[test]
+    fn find_cycle_on_self_loop() {
+        let g = graph(&[("a", "b"), ("b", "b")]);
+        let cycle = g.find_cycle().unwrap();
+        assert_eq!(cycle_names(&g, &cycle), ["b"]);
+    }
+
+    #[test]
+    fn find_cycle_on_multi_node_loop() {
+        let g = graph(&[("x", "a"), ("a", "b"), ("b", "c"), ("c", "a"), ("c", "y")]);
+        let cycle = g.find_cycle().unwrap();
+        assert_eq!(cycle_names(&g, &cycle), ["a", "b", "c"]);
+
+        // Every node has an edge to the next one on the cycle
+        for (i, &node) in cycle.nodes.iter().enumerate() {
+            let next = cycle.nodes[(i + 1) % cycle.nodes.len()];
+            assert!(g.neighbors(node).contains(&next));
+        }
+    }
+
+    #[test]
+    fn find_cycle_without_cycle() {
+        // Two paths joining again are no cycle
+        let g = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+        assert_eq!(g.find_cycle(), None);
+    }
+
+    #[test]
+    fn reachable_from_follows_edges_forward() {
+        let g = graph(&[("a", "b"), ("b", "c"), ("d", "a"), ("e", "e")]);
+        let reachable = g.reachable_from(g.id("a").unwrap());
+        let names = (0..g.len())
+            .filter(|&node| reachable[node])
+            .map(|node| g.name(node))
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        let backwards = g.reversed().reachable_from(g.id("b").unwrap());
+        assert!(backwards[g.id("d").unwrap()] && !backwards[g.id("c").unwrap()]);
+    }
+
+    #[test]
+    fn induced_keeps_ids_and_inner_edges() {
+        let g = graph(&[("a", "b"), ("b", "c"), ("a", "c")]);
+        let b = g.id("b").unwrap();
+        let keep = (0..g.len()).map(|node| node != b).collect::<Vec<bool>>();
+        let sub = g.induced(&keep);
+
+        assert_eq!(sub.len(), g.len());
+        assert_eq!(sub.id("c"), g.id("c"));
+        assert_eq!(sub.neighbors(g.id("a").unwrap()), [g.id("c").unwrap()]);
+        assert!(sub.neighbors(b).is_empty());
+    }
+
+    #[test]
+    fn dp_over_successors_counts_paths() {
+        let g = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("b", "c")]);
+        let d = g.id("d").unwrap();
+        let paths = g.dp_over_successors(|node, paths: &[usize]| {
+            if node == d {
+                return 1;
+            }
+            g.neighbors(node).iter().map(|&next| paths[next]).sum()
+        }).unwrap();
+        assert_eq!(paths[g.id("a").unwrap()], 3);
+        assert_eq!(paths[g.id("b").unwrap()], 2);
+
+        let looping = graph(&[("a", "b"), ("b", "a")]);
+        assert!(looping.dp_over_successors(|_, _: &[usize]| 0).is_err());
+    }
+}
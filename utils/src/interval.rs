@@ -0,0 +1,329 @@
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+
+// Integer-like types that an IntervalSet can hold
+pub trait Discrete: Copy + Ord + Debug {
+    const MIN: Self;
+    const MAX: Self;
+
+    fn succ(self) -> Option<Self>;
+    fn pred(self) -> Option<Self>;
+    // Number of values in the inclusive range [start, end], start <= end
+    fn count(start: Self, end: Self) -> u128;
+}
+
+macro_rules! impl_discrete {
+    ($($t:ty),*) => {
+        $(
+            impl Discrete for $t {
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+
+                fn succ(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn pred(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+
+                fn count(start: Self, end: Self) -> u128 {
+                    (end as i128 - start as i128 + 1) as u128
+                }
+            }
+        )*
+    };
+}
+
+impl_discrete!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+// Whether an interval ending at `end` and one starting at `start` after it become one
+fn joins<T: Discrete>(end: T, start: T, adjacent_merge: bool) -> bool {
+    start <= end || adjacent_merge && end.succ() == Some(start)
+}
+
+// Set of values stored as sorted, inclusive intervals. Overlapping intervals are merged on insert,
+// so every value belongs to exactly one stored interval. Touching intervals like 3-5 and 6-8 are
+// merged into 3-8 as well, unless the set is built with `adjacent_merge` turned off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    intervals: Vec<(T, T)>,
+    adjacent_merge: bool,
+}
+
+impl<T: Discrete> Default for IntervalSet<T> {
+    fn default() -> Self {
+        IntervalSet::new()
+    }
+}
+
+impl<T: Discrete> IntervalSet<T> {
+    pub fn new() -> IntervalSet<T> {
+        IntervalSet::with_adjacent_merge(true)
+    }
+
+    pub fn with_adjacent_merge(adjacent_merge: bool) -> IntervalSet<T> {
+        IntervalSet { intervals: Vec::new(), adjacent_merge }
+    }
+
+    pub fn from_ranges(ranges: impl IntoIterator<Item = RangeInclusive<T>>, adjacent_merge: bool) -> IntervalSet<T> {
+        let intervals = ranges.into_iter().map(|r| r.into_inner()).collect();
+        IntervalSet::from_unsorted(intervals, adjacent_merge)
+    }
+
+    // Builds the set with one sort and sweep instead of repeated inserts
+    fn from_unsorted(mut intervals: Vec<(T, T)>, adjacent_merge: bool) -> IntervalSet<T> {
+        intervals.retain(|(start, end)| start <= end);
+        intervals.sort();
+
+        let mut merged: Vec<(T, T)> = Vec::with_capacity(intervals.len());
+        for (start, end) in intervals {
+            match merged.last_mut() {
+                Some(last) if joins(last.1, start, adjacent_merge) => {
+                    last.1 = last.1.max(end);
+                },
+                _ => merged.push((start, end)),
+            }
+        }
+        IntervalSet { intervals: merged, adjacent_merge }
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let (start, end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // Stored intervals in lo..hi overlap the new one, or touch it with `adjacent_merge`
+        let lo = self.intervals.partition_point(|&(_, e)| !joins(e, start, self.adjacent_merge));
+        let hi = self.intervals.partition_point(|&(s, _)| joins(end, s, self.adjacent_merge));
+
+        if lo < hi {
+            let merged = (start.min(self.intervals[lo].0), end.max(self.intervals[hi - 1].1));
+            self.intervals.splice(lo..hi, [merged]);
+        }
+        else {
+            self.intervals.insert(lo, (start, end));
+        }
+    }
+
+    // O(log n) lookup
+    pub fn contains(&self, value: T) -> bool {
+        self.find(value).is_some()
+    }
+
+    // The stored interval containing `value`, if any
+    pub fn find(&self, value: T) -> Option<RangeInclusive<T>> {
+        let idx = self.intervals.partition_point(|&(_, e)| e < value);
+        self.intervals.get(idx)
+            .filter(|&&(s, _)| s <= value)
+            .map(|&(s, e)| s..=e)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    // Total number of values covered by the set
+    pub fn covered_len(&self) -> u128 {
+        self.intervals.iter()
+            .map(|&(s, e)| T::count(s, e))
+            .sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.intervals.iter().map(|&(s, e)| s..=e)
+    }
+
+    // Uncovered ranges between consecutive intervals
+    pub fn gaps(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.intervals.windows(2)
+            .map(|pair| {
+                // Stored intervals never overlap, so both bounds exist
+                pair[0].1.succ().unwrap()..=pair[1].0.pred().unwrap()
+            })
+            // Touching intervals, kept apart without `adjacent_merge`, leave no gap
+            .filter(|gap| !gap.is_empty())
+    }
+
+    // Set operations keep this set's `adjacent_merge`, `other` may use either mode
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut intervals = self.intervals.clone();
+        intervals.extend_from_slice(&other.intervals);
+        IntervalSet::from_unsorted(intervals, self.adjacent_merge)
+    }
+
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            let start = a.0.max(b.0);
+            let end = a.1.min(b.1);
+            if start <= end {
+                intervals.push((start, end));
+            }
+            if a.1 < b.1 {
+                i += 1;
+            }
+            else {
+                j += 1;
+            }
+        }
+        // Pieces cut from touching intervals of `other` may touch, merge them if this set does
+        IntervalSet::from_unsorted(intervals, self.adjacent_merge)
+    }
+
+    pub fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        self.intersection(&other.complement(T::MIN..=T::MAX))
+    }
+
+    // Everything within `bounds` that is not in the set
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> IntervalSet<T> {
+        let (lo, hi) = bounds.into_inner();
+        let mut intervals = Vec::new();
+        if lo > hi {
+            return IntervalSet { intervals, adjacent_merge: self.adjacent_merge };
+        }
+
+        // Smallest value that may still be uncovered, None once we passed T::MAX
+        let mut cursor = Some(lo);
+        for &(s, e) in &self.intervals {
+            let Some(c) = cursor else { break };
+            if s > hi {
+                break;
+            }
+            if e < c {
+                continue;
+            }
+            if s > c {
+                intervals.push((c, s.pred().unwrap()));
+            }
+            cursor = e.succ();
+        }
+        if let Some(c) = cursor && c <= hi {
+            intervals.push((c, hi));
+        }
+        IntervalSet { intervals, adjacent_merge: self.adjacent_merge }
+    }
+}
+
+impl<T: Discrete> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        IntervalSet::from_ranges(iter, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges<T: Discrete>(set: &IntervalSet<T>) -> Vec<RangeInclusive<T>> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn insert_merges_overlapping_and_touching() {
+        let mut set = IntervalSet::new();
+        set.insert(10..=20);
+        set.insert(30..=40);
+        set.insert(15..=25);
+        assert_eq!(ranges(&set), [10..=25, 30..=40]);
+
+        set.insert(26..=29);
+        assert_eq!(ranges(&set), [10..=40]);
+
+        set.insert(5..=8);
+        set.insert(1..=100);
+        assert_eq!(ranges(&set), [1..=100]);
+        assert!(set.contains(1) && set.contains(100) && !set.contains(101));
+    }
+
+    #[test]
+    fn insert_keeps_touching_apart_without_adjacent_merge() {
+        let mut set = IntervalSet::with_adjacent_merge(false);
+        set.insert(6..=8);
+        set.insert(3..=5);
+        set.insert(7..=9);
+        assert_eq!(ranges(&set), [3..=5, 6..=9]);
+        assert_eq!(set.gaps().count(), 0);
+        assert_eq!(set.find(6), Some(6..=9));
+    }
+
+    #[test]
+    fn insert_at_the_type_bounds() {
+        let mut set = IntervalSet::new();
+        set.insert(250..=u8::MAX);
+        set.insert(0..=3);
+        set.insert(4..=249);
+        assert_eq!(ranges(&set), [0..=u8::MAX]);
+    }
+
+    #[test]
+    fn complement_of_full_u8_range() {
+        let set: IntervalSet<u8> = [0..=9, 100..=110, 250..=255].into_iter().collect();
+        let complement = set.complement(u8::MIN..=u8::MAX);
+        assert_eq!(ranges(&complement), [10..=99, 111..=249]);
+        assert_eq!(ranges(&complement.complement(u8::MIN..=u8::MAX)), ranges(&set));
+
+        assert_eq!(ranges(&IntervalSet::<u8>::new().complement(u8::MIN..=u8::MAX)), [0..=255]);
+        let full: IntervalSet<u8> = [0..=255].into_iter().collect();
+        assert!(full.complement(u8::MIN..=u8::MAX).is_empty());
+    }
+
+    #[test]
+    fn complement_within_bounds() {
+        let set: IntervalSet<i32> = [-5..=5, 20..=30].into_iter().collect();
+        assert_eq!(ranges(&set.complement(0..=25)), [6..=19]);
+    }
+
+    #[test]
+    fn difference_cuts_out_the_other_set() {
+        let a: IntervalSet<u32> = [0..=100, 200..=300].into_iter().collect();
+        let b: IntervalSet<u32> = [50..=60, 90..=210, 300..=400].into_iter().collect();
+        assert_eq!(ranges(&a.difference(&b)), [0..=49, 61..=89, 211..=299]);
+        assert!(a.difference(&a).is_empty());
+        assert_eq!(a.difference(&IntervalSet::new()), a);
+    }
+
+    #[test]
+    fn union_and_intersection() {
+        let a: IntervalSet<i64> = [1..=5, 10..=15].into_iter().collect();
+        let b: IntervalSet<i64> = [4..=9, 20..=20].into_iter().collect();
+        assert_eq!(ranges(&a.union(&b)), [1..=15, 20..=20]);
+        assert_eq!(ranges(&a.intersection(&b)), [4..=5]);
+    }
+
+    #[test]
+    fn set_operations_keep_the_merge_mode() {
+        let merging: IntervalSet<u32> = [1..=10].into_iter().collect();
+        let separate = IntervalSet::from_ranges([3..=5, 6..=8], false);
+
+        assert_eq!(ranges(&merging.intersection(&separate)), [3..=8]);
+        assert_eq!(ranges(&separate.intersection(&merging)), [3..=5, 6..=8]);
+        assert_eq!(ranges(&merging.union(&separate)), [1..=10]);
+        assert_eq!(ranges(&IntervalSet::new().union(&separate)), [3..=8]);
+        assert_eq!(ranges(&merging.difference(&IntervalSet::from_ranges([5..=5, 6..=6], false))), [1..=4, 7..=10]);
+    }
+
+    #[test]
+    fn gaps_between_intervals() {
+        let set: IntervalSet<u64> = [1..=3, 7..=7, 9..=12].into_iter().collect();
+        assert_eq!(set.gaps().collect::<Vec<_>>(), [4..=6, 8..=8]);
+        assert_eq!(IntervalSet::<u64>::new().gaps().count(), 0);
+    }
+
+    #[test]
+    fn covered_len_of_full_u64_range() {
+        let full: IntervalSet<u64> = [0..=u64::MAX].into_iter().collect();
+        assert_eq!(full.covered_len(), u64::MAX as u128 + 1);
+
+        let halves: IntervalSet<u64> = [u64::MAX / 2 + 1..=u64::MAX, 0..=u64::MAX / 2].into_iter().collect();
+        assert_eq!(ranges(&halves), [0..=u64::MAX]);
+        assert_eq!(halves.covered_len(), 1 << 64);
+
+        let signed: IntervalSet<i64> = [i64::MIN..=i64::MAX].into_iter().collect();
+        assert_eq!(signed.covered_len(), 1 << 64);
+    }
+}
@@ -1,10 +1,22 @@
-use std::fs;
+pub mod graph;
+pub mod interval;
+pub mod rng;
+pub mod viz;
+
+use std::fs::{self, File};
+use std::io::BufReader;
 
 pub fn read_input(filename: &str) -> String {
     fs::read_to_string(filename)
         .expect("Something went wrong reading the file")
 }
 
+pub fn open_input(filename: &str) -> BufReader<File> {
+    let file = File::open(filename)
+        .expect("Something went wrong opening the file");
+    BufReader::new(file)
+}
+
 pub fn read_lines(filename: &str) -> Vec<String> {
     read_input(filename)
         .lines()
@@ -17,4 +29,14 @@ pub fn read_lines(filename: &str) -> Vec<String> {
             }
         })
         .collect()
-}
\ No newline at end of file
+}
+
+pub fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+pub fn flag_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
+}
@@ -0,0 +1,40 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Small xorshift generator, good enough for randomized checks without pulling in a dependency
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // A zero state would only ever produce zeros
+        XorShift { state: seed.max(1) }
+    }
+
+    pub fn from_time() -> XorShift {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before the unix epoch")
+            .as_nanos();
+        XorShift::new(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    // Uniform value in the half-open range [low, high)
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        assert!(low < high, "Empty range");
+        low + self.next_u64() % (high - low)
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
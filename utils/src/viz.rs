@@ -0,0 +1,207 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::{flag_value, has_flag};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Default,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Gray,
+}
+
+impl Color {
+    fn ansi_code(&self) -> &'static str {
+        match self {
+            Color::Default => "0",
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Blue => "34",
+            Color::Magenta => "35",
+            Color::Cyan => "36",
+            Color::Gray => "90",
+        }
+    }
+
+    // Default renders as the background in images
+    fn rgb(&self) -> [u8; 3] {
+        match self {
+            Color::Default => [0, 0, 0],
+            Color::Red => [220, 50, 47],
+            Color::Green => [133, 153, 0],
+            Color::Yellow => [181, 137, 0],
+            Color::Blue => [38, 139, 210],
+            Color::Magenta => [211, 54, 130],
+            Color::Cyan => [42, 161, 152],
+            Color::Gray => [88, 110, 117],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Color,
+}
+
+impl Cell {
+    pub fn new(ch: char, color: Color) -> Cell {
+        Cell { ch, color }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame { width, height, cells: vec![Cell::new('.', Color::Default); width * height] }
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, cell: Cell) {
+        self.cells[row * self.width + col] = cell;
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Cell {
+        self.cells[row * self.width + col]
+    }
+
+    // A frame without columns has no cells and so no rows, `chunks` would panic on a width of 0
+    fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn to_text(&self) -> String {
+        self.rows()
+            .map(|row| row.iter().map(|cell| cell.ch).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for row in self.rows() {
+            let mut current = Color::Default;
+            for cell in row {
+                if cell.color != current {
+                    out.push_str(&format!("\x1b[{}m", cell.color.ansi_code()));
+                    current = cell.color;
+                }
+                out.push(cell.ch);
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+
+    // Binary PPM where every cell becomes a `scale` x `scale` block of its color
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width * scale, self.height * scale).into_bytes();
+        for row in self.rows() {
+            let line = row.iter()
+                .flat_map(|cell| cell.color.rgb().repeat(scale))
+                .collect::<Vec<u8>>();
+            for _ in 0..scale {
+                out.extend_from_slice(&line);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FrameFormat {
+    Text,
+    Ppm,
+}
+
+#[derive(Debug)]
+pub enum Output {
+    // Plays frames in the terminal, waiting for enter between frames when stepping
+    Terminal { fps: f64, step: bool },
+    Directory { path: PathBuf, format: FrameFormat },
+}
+
+pub struct Visualizer {
+    output: Output,
+    frame_no: usize,
+}
+
+impl Visualizer {
+    pub fn new(output: Output) -> Visualizer {
+        Visualizer { output, frame_no: 0 }
+    }
+
+    // Enabled by `--visualize`, configured with `--fps N`, `--step` or `--frames-dir DIR [--format txt|ppm]`
+    pub fn from_args() -> Option<Visualizer> {
+        if !has_flag("--visualize") {
+            return None;
+        }
+
+        let output = match flag_value("--frames-dir") {
+            Some(path) => {
+                let format = match flag_value("--format").as_deref() {
+                    None | Some("txt") => FrameFormat::Text,
+                    Some("ppm") => FrameFormat::Ppm,
+                    Some(other) => panic!("Unknown frame format: {other}"),
+                };
+                Output::Directory { path: PathBuf::from(path), format }
+            },
+            None => {
+                let fps = flag_value("--fps")
+                    .map(|s| s.parse::<f64>().expect("Could not parse frame rate"))
+                    .unwrap_or(10.0);
+                assert!(fps > 0.0, "Frame rate must be positive");
+                Output::Terminal { fps, step: has_flag("--step") }
+            },
+        };
+        Some(Visualizer::new(output))
+    }
+
+    pub fn show(&mut self, frame: &Frame) -> io::Result<()> {
+        match &self.output {
+            Output::Terminal { fps, step } => {
+                let mut stdout = io::stdout();
+                // Clear the screen and move the cursor to the top left
+                write!(stdout, "\x1b[2J\x1b[H{}", frame.to_ansi())?;
+                writeln!(stdout, "Frame {}", self.frame_no)?;
+                stdout.flush()?;
+
+                if *step {
+                    io::stdin().read_line(&mut String::new())?;
+                }
+                else {
+                    thread::sleep(Duration::from_secs_f64(1.0 / fps));
+                }
+            },
+            Output::Directory { path, format } => {
+                fs::create_dir_all(path)?;
+                match format {
+                    FrameFormat::Text => {
+                        let file = path.join(format!("frame_{:05}.txt", self.frame_no));
+                        fs::write(file, frame.to_text())?;
+                    },
+                    FrameFormat::Ppm => {
+                        let file = path.join(format!("frame_{:05}.ppm", self.frame_no));
+                        fs::write(file, frame.to_ppm(4))?;
+                    },
+                }
+            },
+        }
+        self.frame_no += 1;
+        Ok(())
+    }
+}